@@ -0,0 +1,68 @@
+use serde::Serialize;
+
+/// API features that differ between AdGuard Home releases.
+///
+/// Derived from the version reported by `/control/status` so the client can
+/// pick endpoints and payload shapes without trial-and-error 404s.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: Option<String>,
+    /// `/control/querylog/config` (interval in ms) instead of `/control/querylog_info` (days).
+    pub querylog_config_v1: bool,
+    /// `/control/stats/config` (interval in ms) instead of `/control/stats_info` (days).
+    pub stats_config_v1: bool,
+    /// `/control/blocked_services/get|update` with a weekly schedule.
+    pub blocked_services_schedule: bool,
+    /// `POST /control/clients/search` instead of `GET /control/clients/find`.
+    pub clients_search: bool,
+    /// `PUT /control/rewrite/update`.
+    pub rewrite_update: bool,
+}
+
+impl Capabilities {
+    pub fn for_version(version: &str) -> Self {
+        let Ok(parsed) = semver::Version::parse(version.trim().trim_start_matches('v')) else {
+            return Self {
+                version: Some(version.to_string()),
+                ..Self::assumed()
+            };
+        };
+        let at_least = |minor: u64, patch: u64| {
+            (parsed.major, parsed.minor, parsed.patch) >= (0, minor, patch)
+        };
+
+        Self {
+            version: Some(version.to_string()),
+            querylog_config_v1: at_least(107, 30),
+            stats_config_v1: at_least(107, 30),
+            blocked_services_schedule: at_least(107, 37),
+            clients_search: at_least(107, 56),
+            rewrite_update: at_least(107, 33),
+        }
+    }
+
+    /// Used when the version could not be probed: keeps the endpoints this
+    /// client has always called.
+    pub fn assumed() -> Self {
+        Self {
+            version: None,
+            querylog_config_v1: true,
+            stats_config_v1: true,
+            blocked_services_schedule: false,
+            clients_search: false,
+            rewrite_update: true,
+        }
+    }
+
+    pub fn require(&self, supported: bool, feature: &str) -> crate::error::Result<()> {
+        if supported {
+            Ok(())
+        } else {
+            Err(crate::error::Error::Unsupported(format!(
+                "{} is not available on AdGuard Home {}",
+                feature,
+                self.version.as_deref().unwrap_or("(unknown version)")
+            )))
+        }
+    }
+}
@@ -1,20 +1,146 @@
+use super::capabilities::Capabilities;
 use super::models::*;
 use crate::config::InstanceConfig;
 use crate::error::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
 
 #[derive(Debug, Clone)]
 pub struct AdGuardClient {
     pub client: reqwest::Client,
     pub config: InstanceConfig,
+    capabilities: Arc<OnceCell<Capabilities>>,
+}
+
+/// Clients keyed by instance, shared by every clone of the config so each
+/// instance is only probed for capabilities once.
+#[derive(Debug, Clone, Default)]
+pub struct ClientCache(Arc<dashmap::DashMap<String, AdGuardClient>>);
+
+impl ClientCache {
+    pub fn get(&self, instance: &InstanceConfig) -> Result<AdGuardClient> {
+        let key = format!(
+            "{}@{}",
+            instance.name.as_deref().unwrap_or_default(),
+            instance.url
+        );
+        // Ad-hoc replicas can reuse a URL with other credentials
+        if let Some(client) = self.0.get(&key)
+            && client.config == *instance
+        {
+            return Ok(client.clone());
+        }
+        let client = AdGuardClient::try_new(instance.clone())?;
+        self.0.insert(key, client.clone());
+        Ok(client)
+    }
 }
 
 impl AdGuardClient {
     pub fn new(config: InstanceConfig) -> Self {
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(config.no_verify_ssl.unwrap_or(true))
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
-        Self { client, config }
+        let client = Self::build_http_client(&config).unwrap_or_else(|e| {
+            tracing::error!("Failed to build HTTP client for {}: {}", config.url, e);
+            reqwest::Client::new()
+        });
+        Self {
+            client,
+            config,
+            capabilities: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// Like `new`, but surfaces invalid headers, unreadable certificate files
+    /// and bad proxy URLs instead of falling back to a default client.
+    pub fn try_new(config: InstanceConfig) -> Result<Self> {
+        let client = Self::build_http_client(&config)?;
+        Ok(Self {
+            client,
+            config,
+            capabilities: Arc::new(OnceCell::new()),
+        })
+    }
+
+    /// Probes the instance version once and caches the derived capability set.
+    /// A failed probe is not cached, so the next call retries.
+    pub async fn capabilities(&self) -> Capabilities {
+        let probed = self
+            .capabilities
+            .get_or_try_init(|| async {
+                let status = self.get_status().await?;
+                Ok::<_, crate::error::Error>(Capabilities::for_version(&status.version))
+            })
+            .await;
+        match probed {
+            Ok(caps) => caps.clone(),
+            Err(e) => {
+                tracing::debug!("Failed to probe capabilities of {}: {}", self.config.url, e);
+                Capabilities::assumed()
+            }
+        }
+    }
+
+    fn build_http_client(config: &InstanceConfig) -> Result<reqwest::Client> {
+        use crate::error::Error;
+        use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+        let mut builder = reqwest::Client::builder()
+            .danger_accept_invalid_certs(config.no_verify_ssl.unwrap_or(true));
+
+        if !config.headers.is_empty() {
+            let mut headers = HeaderMap::new();
+            for (name, value) in &config.headers {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| Error::Config(format!("Invalid header name {}: {}", name, e)))?;
+                let mut value = HeaderValue::from_str(value).map_err(|e| {
+                    Error::Config(format!("Invalid value for header {}: {}", name, e))
+                })?;
+                value.set_sensitive(true);
+                headers.insert(name, value);
+            }
+            builder = builder.default_headers(headers);
+        }
+
+        if let Some(ca_path) = &config.ca_cert_path {
+            let pem = std::fs::read(ca_path)?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        match (&config.client_cert_path, &config.client_key_path) {
+            (Some(cert_path), key_path) => {
+                // Identity::from_pem expects the certificate and key in one buffer
+                let mut pem = std::fs::read(cert_path)?;
+                if let Some(key_path) = key_path {
+                    pem.push(b'\n');
+                    pem.extend(std::fs::read(key_path)?);
+                }
+                builder = builder.identity(reqwest::Identity::from_pem(&pem)?);
+            }
+            (None, Some(_)) => {
+                return Err(Error::Config(
+                    "client_key_path requires client_cert_path".to_string(),
+                ));
+            }
+            (None, None) => {}
+        }
+
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Joins an API path onto the instance URL, preserving any sub-path
+    /// prefix (e.g. `https://proxy/adguard`) without doubling slashes.
+    fn endpoint(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.config.url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
     }
 
     fn add_auth(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
@@ -28,7 +154,7 @@ impl AdGuardClient {
     }
 
     pub async fn get_version_info(&self) -> Result<VersionInfo> {
-        let url = format!("{}/control/version_info", self.config.url);
+        let url = self.endpoint("/control/version_info");
         let request = self.add_auth(self.client.get(&url));
 
         match request.send().await {
@@ -55,7 +181,7 @@ impl AdGuardClient {
             ));
         }
 
-        let url = format!("{}/control/update", self.config.url);
+        let url = self.endpoint("/control/update");
         let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
@@ -63,7 +189,17 @@ impl AdGuardClient {
     }
 
     pub async fn get_query_log_config(&self) -> Result<QueryLogConfig> {
-        let url = format!("{}/control/querylog/config", self.config.url);
+        if !self.capabilities().await.querylog_config_v1 {
+            let url = self.endpoint("/control/querylog_info");
+            let request = self.add_auth(self.client.get(&url));
+
+            let response = request.send().await?.error_for_status()?;
+            let mut config = response.json::<QueryLogConfig>().await?;
+            config.interval = days_to_ms(config.interval);
+            return Ok(config);
+        }
+
+        let url = self.endpoint("/control/querylog/config");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -71,8 +207,18 @@ impl AdGuardClient {
         Ok(config)
     }
 
-    pub async fn set_query_log_config(&self, config: QueryLogConfig) -> Result<()> {
-        let url = format!("{}/control/querylog/config/update", self.config.url);
+    pub async fn set_query_log_config(&self, mut config: QueryLogConfig) -> Result<()> {
+        let caps = self.capabilities().await;
+        if !caps.querylog_config_v1 {
+            config.interval = legacy_retention_days(config.interval, &caps)?;
+            let url = self.endpoint("/control/querylog_config");
+            let request = self.add_auth(self.client.post(&url).json(&config));
+
+            request.send().await?.error_for_status()?;
+            return Ok(());
+        }
+
+        let url = self.endpoint("/control/querylog/config/update");
         let request = self.add_auth(self.client.put(&url).json(&config));
 
         request.send().await?.error_for_status()?;
@@ -80,7 +226,7 @@ impl AdGuardClient {
     }
 
     pub async fn get_safe_search_settings(&self) -> Result<SafeSearchConfig> {
-        let url = format!("{}/control/safesearch/status", self.config.url);
+        let url = self.endpoint("/control/safesearch/status");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -89,7 +235,7 @@ impl AdGuardClient {
     }
 
     pub async fn set_safe_search_settings(&self, settings: SafeSearchConfig) -> Result<()> {
-        let url = format!("{}/control/safesearch/settings", self.config.url);
+        let url = self.endpoint("/control/safesearch/settings");
         let request = self.add_auth(self.client.put(&url).json(&settings));
 
         request.send().await?.error_for_status()?;
@@ -97,7 +243,7 @@ impl AdGuardClient {
     }
 
     pub async fn get_parental_settings(&self) -> Result<ParentalControlConfig> {
-        let url = format!("{}/control/parental/status", self.config.url);
+        let url = self.endpoint("/control/parental/status");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -115,7 +261,7 @@ impl AdGuardClient {
     }
 
     pub async fn get_status(&self) -> Result<Status> {
-        let url = format!("{}/control/status", self.config.url);
+        let url = self.endpoint("/control/status");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -124,49 +270,158 @@ impl AdGuardClient {
     }
 
     pub async fn get_stats(&self, time_period: Option<&str>) -> Result<Stats> {
-        let mut url = format!("{}/control/stats", self.config.url);
+        let url = self.endpoint("/control/stats");
+        let mut request = self.client.get(&url);
         if let Some(period) = time_period {
-            url.push_str(&format!("?time_period={}", period));
+            request = request.query(&[("time_period", period)]);
         }
-        let request = self.add_auth(self.client.get(&url));
+        let request = self.add_auth(request);
 
         let response = request.send().await?.error_for_status()?;
         let stats = response.json::<Stats>().await?;
         Ok(stats)
     }
 
+    pub async fn get_stats_config(&self) -> Result<StatsConfig> {
+        if !self.capabilities().await.stats_config_v1 {
+            let url = self.endpoint("/control/stats_info");
+            let request = self.add_auth(self.client.get(&url));
+
+            let response = request.send().await?.error_for_status()?;
+            let mut config = response.json::<StatsConfig>().await?;
+            config.interval = days_to_ms(config.interval);
+            return Ok(config);
+        }
+
+        let url = self.endpoint("/control/stats/config");
+        let request = self.add_auth(self.client.get(&url));
+
+        let response = request.send().await?.error_for_status()?;
+        let config = response.json::<StatsConfig>().await?;
+        Ok(config)
+    }
+
+    pub async fn set_stats_config(&self, mut config: StatsConfig) -> Result<()> {
+        let caps = self.capabilities().await;
+        if !caps.stats_config_v1 {
+            // The legacy endpoint only takes the retention interval
+            caps.require(
+                config.enabled && config.ignored.is_empty(),
+                "Disabling statistics or ignoring domains",
+            )?;
+            config.interval = legacy_retention_days(config.interval, &caps)?;
+            let url = self.endpoint("/control/stats_config");
+            let request = self.add_auth(
+                self.client
+                    .post(&url)
+                    .json(&serde_json::json!({ "interval": config.interval })),
+            );
+
+            request.send().await?.error_for_status()?;
+            return Ok(());
+        }
+
+        let url = self.endpoint("/control/stats/config/update");
+        let request = self.add_auth(self.client.put(&url).json(&config));
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
     pub async fn get_query_log(
         &self,
         search: Option<&str>,
         filter: Option<&str>,
         limit: Option<u32>,
     ) -> Result<QueryLogResponse> {
-        let mut url = format!("{}/control/querylog", self.config.url);
-        let mut params = Vec::new();
-        if let Some(s) = search {
-            params.push(format!("search={}", s));
-        }
-        if let Some(f) = filter {
-            params.push(format!("filter={}", f));
-        }
-        if let Some(l) = limit {
-            params.push(format!("limit={}", l));
+        self.query_log(&QueryLogParams {
+            search: search.map(|s| s.to_string()),
+            response_status: filter.map(|f| f.to_string()),
+            limit,
+            ..Default::default()
+        })
+        .await
+    }
+
+    pub async fn query_log(&self, params: &QueryLogParams) -> Result<QueryLogResponse> {
+        if let Some(status) = params.response_status.as_deref()
+            && !QUERY_LOG_RESPONSE_STATUSES.contains(&status)
+        {
+            return Err(crate::error::Error::Generic(format!(
+                "Unknown query log response_status: {}",
+                status
+            )));
         }
 
-        if !params.is_empty() {
-            url.push('?');
-            url.push_str(&params.join("&"));
+        let mut query = params.clone();
+        // Let the server narrow the page down before the exact client match below.
+        if query.search.is_none() {
+            query.search = params.client.clone();
         }
 
-        let request = self.add_auth(self.client.get(&url));
+        let url = self.endpoint("/control/querylog");
+        let request = self.add_auth(self.client.get(&url).query(&query));
 
         let response = request.send().await?.error_for_status()?;
-        let log = response.json::<QueryLogResponse>().await?;
+        let mut log = response.json::<QueryLogResponse>().await?;
+        if let Some(client) = params.client.as_deref() {
+            log.data.retain(|entry| {
+                entry.client == client
+                    || entry
+                        .client_info
+                        .as_ref()
+                        .is_some_and(|info| info.name.eq_ignore_ascii_case(client))
+            });
+        }
         Ok(log)
     }
 
+    /// Streams query log pages, newest first, following the `oldest` cursor
+    /// until the log is exhausted or entries fall before `until`.
+    pub fn query_log_pages(
+        &self,
+        params: QueryLogParams,
+        until: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> impl futures::Stream<Item = Result<Vec<QueryLogEntry>>> + '_ {
+        futures::stream::try_unfold(Some(params), move |state| async move {
+            let Some(mut params) = state else {
+                return Ok(None);
+            };
+            let page = self.query_log(&params).await?;
+            let mut entries = page.data;
+            let mut reached_bound = false;
+            if let Some(until) = until {
+                let before = entries.len();
+                entries.retain(|entry| {
+                    chrono::DateTime::parse_from_rfc3339(&entry.time)
+                        .map(|t| t >= until)
+                        .unwrap_or(true)
+                });
+                reached_bound = entries.len() < before
+                    || chrono::DateTime::parse_from_rfc3339(&page.oldest)
+                        .is_ok_and(|oldest| oldest < until);
+            }
+
+            let next = if page.oldest.is_empty()
+                || reached_bound
+                || params.older_than.as_deref() == Some(page.oldest.as_str())
+            {
+                None
+            } else {
+                params.older_than = Some(page.oldest);
+                params.offset = None;
+                Some(params)
+            };
+
+            if entries.is_empty() && next.is_none() {
+                return Ok(None);
+            }
+            Ok(Some((entries, next)))
+        })
+    }
+
     pub async fn list_rewrites(&self) -> Result<Vec<DnsRewrite>> {
-        let url = format!("{}/control/rewrite/list", self.config.url);
+        let url = self.endpoint("/control/rewrite/list");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -175,7 +430,7 @@ impl AdGuardClient {
     }
 
     pub async fn add_rewrite(&self, rewrite: DnsRewrite) -> Result<()> {
-        let url = format!("{}/control/rewrite/add", self.config.url);
+        let url = self.endpoint("/control/rewrite/add");
         let request = self.add_auth(self.client.post(&url).json(&rewrite));
 
         request.send().await?.error_for_status()?;
@@ -183,15 +438,39 @@ impl AdGuardClient {
     }
 
     pub async fn delete_rewrite(&self, rewrite: DnsRewrite) -> Result<()> {
-        let url = format!("{}/control/rewrite/delete", self.config.url);
+        let url = self.endpoint("/control/rewrite/delete");
         let request = self.add_auth(self.client.post(&url).json(&rewrite));
 
         request.send().await?.error_for_status()?;
         Ok(())
     }
 
+    /// Replaces `target` with `update` in place. Instances without
+    /// `/control/rewrite/update` get the new rewrite added before the old one is
+    /// deleted, so the name never goes unanswered.
+    pub async fn update_rewrite(&self, target: DnsRewrite, update: DnsRewrite) -> Result<()> {
+        // The fallback's delete would remove the very rewrite it just re-added
+        if target == update {
+            return Ok(());
+        }
+        if !self.capabilities().await.rewrite_update {
+            self.add_rewrite(update).await?;
+            return self.delete_rewrite(target).await;
+        }
+
+        let url = self.endpoint("/control/rewrite/update");
+        let request = self.add_auth(
+            self.client
+                .put(&url)
+                .json(&UpdateRewriteRequest { target, update }),
+        );
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
     pub async fn set_protection(&self, enabled: bool) -> Result<()> {
-        let url = format!("{}/control/protection", self.config.url);
+        let url = self.endpoint("/control/protection");
         let request = self.add_auth(
             self.client
                 .post(&url)
@@ -204,7 +483,7 @@ impl AdGuardClient {
 
     pub async fn set_safe_search(&self, enabled: bool) -> Result<()> {
         let path = if enabled { "enable" } else { "disable" };
-        let url = format!("{}/control/safesearch/{}", self.config.url, path);
+        let url = self.endpoint(&format!("/control/safesearch/{}", path));
         let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
@@ -213,7 +492,7 @@ impl AdGuardClient {
 
     pub async fn set_safe_browsing(&self, enabled: bool) -> Result<()> {
         let path = if enabled { "enable" } else { "disable" };
-        let url = format!("{}/control/safebrowsing/{}", self.config.url, path);
+        let url = self.endpoint(&format!("/control/safebrowsing/{}", path));
         let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
@@ -222,7 +501,7 @@ impl AdGuardClient {
 
     pub async fn set_parental_control(&self, enabled: bool) -> Result<()> {
         let path = if enabled { "enable" } else { "disable" };
-        let url = format!("{}/control/parental/{}", self.config.url, path);
+        let url = self.endpoint(&format!("/control/parental/{}", path));
         let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
@@ -230,7 +509,7 @@ impl AdGuardClient {
     }
 
     pub async fn list_filters(&self) -> Result<FilteringConfig> {
-        let url = format!("{}/control/filtering/status", self.config.url);
+        let url = self.endpoint("/control/filtering/status");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -238,8 +517,33 @@ impl AdGuardClient {
         Ok(config)
     }
 
+    /// Re-downloads blocklists (`whitelist: false`) or allowlists; returns how many changed.
+    pub async fn refresh_filters(&self, whitelist: bool) -> Result<u32> {
+        let url = self.endpoint("/control/filtering/refresh");
+        let request = self.add_auth(
+            self.client
+                .post(&url)
+                .json(&serde_json::json!({ "whitelist": whitelist })),
+        );
+
+        let response = request.send().await?.error_for_status()?;
+        let result = response.json::<RefreshFiltersResponse>().await?;
+        Ok(result.updated)
+    }
+
+    pub async fn set_filtering_config(&self, enabled: bool, interval: u32) -> Result<()> {
+        let url = self.endpoint("/control/filtering/config");
+        let request = self.add_auth(self.client.post(&url).json(&serde_json::json!({
+            "enabled": enabled,
+            "interval": interval
+        })));
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
     pub async fn add_filter(&self, name: String, url: String, whitelist: bool) -> Result<()> {
-        let endpoint = format!("{}/control/filtering/add_url", self.config.url);
+        let endpoint = self.endpoint("/control/filtering/add_url");
         let request = self.add_auth(self.client.post(&endpoint).json(&AddFilterRequest {
             name,
             url,
@@ -251,7 +555,7 @@ impl AdGuardClient {
     }
 
     pub async fn toggle_filter(&self, url: String, name: String, enabled: bool) -> Result<()> {
-        let endpoint = format!("{}/control/filtering/set_url", self.config.url);
+        let endpoint = self.endpoint("/control/filtering/set_url");
         let request = self.add_auth(self.client.post(&endpoint).json(&SetFilterUrlRequest {
             url: url.clone(),
             data: SetFilterUrlData { enabled, name, url },
@@ -262,7 +566,7 @@ impl AdGuardClient {
     }
 
     pub async fn remove_filter(&self, url: String, whitelist: bool) -> Result<()> {
-        let endpoint = format!("{}/control/filtering/remove_url", self.config.url);
+        let endpoint = self.endpoint("/control/filtering/remove_url");
         let request = self.add_auth(
             self.client
                 .post(&endpoint)
@@ -281,7 +585,7 @@ impl AdGuardClient {
         whitelist: bool,
         enabled: bool,
     ) -> Result<()> {
-        let endpoint = format!("{}/control/filtering/set_url", self.config.url);
+        let endpoint = self.endpoint("/control/filtering/set_url");
 
         let request = self.add_auth(self.client.post(&endpoint).json(&UpdateFilterRequest {
             url: current_url,
@@ -298,32 +602,78 @@ impl AdGuardClient {
         Ok(())
     }
 
-    pub async fn list_clients(&self) -> Result<Vec<AdGuardClientDevice>> {
-        let url = format!("{}/control/clients", self.config.url);
+    pub async fn get_clients(&self) -> Result<ClientsResponse> {
+        let url = self.endpoint("/control/clients");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
         let clients_response = response.json::<ClientsResponse>().await?;
-        Ok(clients_response.clients)
+        Ok(clients_response)
+    }
+
+    pub async fn list_clients(&self) -> Result<Vec<AdGuardClientDevice>> {
+        Ok(self.get_clients().await?.clients)
+    }
+
+    pub async fn list_supported_client_tags(&self) -> Result<Vec<String>> {
+        Ok(self.get_clients().await?.supported_tags)
+    }
+
+    pub async fn list_runtime_clients(&self) -> Result<Vec<RuntimeClient>> {
+        Ok(self.get_clients().await?.auto_clients)
     }
 
     pub async fn get_client_info(&self, identifier: &str) -> Result<AdGuardClientDevice> {
         let clients = self.list_clients().await?;
-        clients
+        if let Some(client) = clients
             .into_iter()
             .find(|c| c.name == identifier || c.ids.iter().any(|id| id == identifier))
+        {
+            return Ok(client);
+        }
+
+        // Let AdGuard resolve CIDR, MAC and ClientID matches; a failed lookup
+        // is reported as "not found" like an exact-match miss.
+        self.find_client(identifier)
+            .await
+            .ok()
+            .flatten()
             .ok_or_else(|| {
                 crate::error::Error::Generic(format!("Client not found: {}", identifier))
             })
     }
 
+    pub async fn find_client(&self, identifier: &str) -> Result<Option<AdGuardClientDevice>> {
+        let request = if self.capabilities().await.clients_search {
+            let url = self.endpoint("/control/clients/search");
+            self.client.post(&url).json(&serde_json::json!({
+                "clients": [{ "id": identifier }]
+            }))
+        } else {
+            let url = self.endpoint("/control/clients/find");
+            self.client.get(&url).query(&[("ip0", identifier)])
+        };
+
+        let response = self.add_auth(request).send().await?.error_for_status()?;
+        let entries = response
+            .json::<Vec<std::collections::HashMap<String, serde_json::Value>>>()
+            .await?;
+
+        // Runtime clients come back with an empty name; only persistent ones count
+        Ok(entries
+            .into_iter()
+            .flat_map(|entry| entry.into_values())
+            .filter_map(|v| serde_json::from_value::<AdGuardClientDevice>(v).ok())
+            .find(|c| !c.name.is_empty()))
+    }
+
     pub async fn get_user_rules(&self) -> Result<Vec<String>> {
         let config = self.list_filters().await?;
         Ok(config.user_rules)
     }
 
     pub async fn set_user_rules(&self, rules: Vec<String>) -> Result<()> {
-        let url = format!("{}/control/filtering/set_rules", self.config.url);
+        let url = self.endpoint("/control/filtering/set_rules");
         let request = self.add_auth(self.client.post(&url).json(&SetRulesRequest { rules }));
 
         request.send().await?.error_for_status()?;
@@ -331,7 +681,7 @@ impl AdGuardClient {
     }
 
     pub async fn list_all_services(&self) -> Result<Vec<BlockedService>> {
-        let url = format!("{}/control/blocked_services/all", self.config.url);
+        let url = self.endpoint("/control/blocked_services/all");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -340,7 +690,11 @@ impl AdGuardClient {
     }
 
     pub async fn list_blocked_services(&self) -> Result<Vec<String>> {
-        let url = format!("{}/control/blocked_services/list", self.config.url);
+        if self.capabilities().await.blocked_services_schedule {
+            return Ok(self.get_blocked_services_config().await?.ids);
+        }
+
+        let url = self.endpoint("/control/blocked_services/list");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -349,15 +703,45 @@ impl AdGuardClient {
     }
 
     pub async fn set_blocked_services(&self, ids: Vec<String>) -> Result<()> {
-        let url = format!("{}/control/blocked_services/set", self.config.url);
+        if self.capabilities().await.blocked_services_schedule {
+            // Keep the current schedule; only the blocked ids change
+            let mut config = self.get_blocked_services_config().await?;
+            config.ids = ids;
+            return self.set_blocked_services_config(config).await;
+        }
+
+        let url = self.endpoint("/control/blocked_services/set");
         let request = self.add_auth(self.client.post(&url).json(&ids));
 
         request.send().await?.error_for_status()?;
         Ok(())
     }
 
+    pub async fn get_blocked_services_config(&self) -> Result<BlockedServicesConfig> {
+        let caps = self.capabilities().await;
+        caps.require(caps.blocked_services_schedule, "Blocked services schedule")?;
+
+        let url = self.endpoint("/control/blocked_services/get");
+        let request = self.add_auth(self.client.get(&url));
+
+        let response = request.send().await?.error_for_status()?;
+        let config = response.json::<BlockedServicesConfig>().await?;
+        Ok(config)
+    }
+
+    pub async fn set_blocked_services_config(&self, config: BlockedServicesConfig) -> Result<()> {
+        let caps = self.capabilities().await;
+        caps.require(caps.blocked_services_schedule, "Blocked services schedule")?;
+
+        let url = self.endpoint("/control/blocked_services/update");
+        let request = self.add_auth(self.client.put(&url).json(&config));
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
     pub async fn add_client(&self, client: AdGuardClientDevice) -> Result<()> {
-        let url = format!("{}/control/clients/add", self.config.url);
+        let url = self.endpoint("/control/clients/add");
         let request = self.add_auth(self.client.post(&url).json(&client));
 
         request.send().await?.error_for_status()?;
@@ -365,7 +749,7 @@ impl AdGuardClient {
     }
 
     pub async fn update_client(&self, old_name: String, client: AdGuardClientDevice) -> Result<()> {
-        let url = format!("{}/control/clients/update", self.config.url);
+        let url = self.endpoint("/control/clients/update");
         let request = self.add_auth(self.client.post(&url).json(&UpdateClientRequest {
             name: old_name,
             data: client,
@@ -376,7 +760,7 @@ impl AdGuardClient {
     }
 
     pub async fn delete_client(&self, name: String) -> Result<()> {
-        let url = format!("{}/control/clients/delete", self.config.url);
+        let url = self.endpoint("/control/clients/delete");
         let request = self.add_auth(self.client.post(&url).json(&DeleteClientRequest { name }));
 
         request.send().await?.error_for_status()?;
@@ -384,7 +768,7 @@ impl AdGuardClient {
     }
 
     pub async fn get_dhcp_status(&self) -> Result<DhcpStatus> {
-        let url = format!("{}/control/dhcp/status", self.config.url);
+        let url = self.endpoint("/control/dhcp/status");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -393,15 +777,63 @@ impl AdGuardClient {
     }
 
     pub async fn set_dhcp_config(&self, config: DhcpStatus) -> Result<()> {
-        let url = format!("{}/control/dhcp/set_config", self.config.url);
-        let request = self.add_auth(self.client.post(&url).json(&config));
+        let url = self.endpoint("/control/dhcp/set_config");
+        let request = self.add_auth(self.client.post(&url).json(&DhcpConfig::from(&config)));
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn list_dhcp_interfaces(&self) -> Result<Vec<NetInterface>> {
+        let url = self.endpoint("/control/dhcp/interfaces");
+        let request = self.add_auth(self.client.get(&url));
+
+        let response = request.send().await?.error_for_status()?;
+        let interfaces = response.json::<HashMap<String, NetInterface>>().await?;
+        let mut interfaces: Vec<_> = interfaces
+            .into_iter()
+            .map(|(name, mut iface)| {
+                if iface.name.is_empty() {
+                    iface.name = name;
+                }
+                iface
+            })
+            .collect();
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(interfaces)
+    }
+
+    pub async fn find_active_dhcp(&self, interface: &str) -> Result<DhcpSearchResult> {
+        let url = self.endpoint("/control/dhcp/find_active_dhcp");
+        let request = self.add_auth(
+            self.client
+                .post(&url)
+                .json(&serde_json::json!({ "interface": interface })),
+        );
+
+        let response = request.send().await?.error_for_status()?;
+        let result = response.json::<DhcpSearchResult>().await?;
+        Ok(result)
+    }
+
+    pub async fn reset_dhcp(&self) -> Result<()> {
+        let url = self.endpoint("/control/dhcp/reset");
+        let request = self.add_auth(self.client.post(&url));
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn reset_dhcp_leases(&self) -> Result<()> {
+        let url = self.endpoint("/control/dhcp/reset_leases");
+        let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
         Ok(())
     }
 
     pub async fn get_profile_info(&self) -> Result<ProfileInfo> {
-        let url = format!("{}/control/profile", self.config.url);
+        let url = self.endpoint("/control/profile");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -410,7 +842,7 @@ impl AdGuardClient {
     }
 
     pub async fn set_profile_info(&self, profile: ProfileInfo) -> Result<()> {
-        let url = format!("{}/control/profile/update", self.config.url);
+        let url = self.endpoint("/control/profile/update");
         let request = self.add_auth(self.client.put(&url).json(&profile));
 
         request.send().await?.error_for_status()?;
@@ -418,7 +850,7 @@ impl AdGuardClient {
     }
 
     pub async fn add_static_lease(&self, lease: StaticLease) -> Result<()> {
-        let url = format!("{}/control/dhcp/add_static_lease", self.config.url);
+        let url = self.endpoint("/control/dhcp/add_static_lease");
         let request = self.add_auth(self.client.post(&url).json(&lease));
 
         request.send().await?.error_for_status()?;
@@ -426,15 +858,49 @@ impl AdGuardClient {
     }
 
     pub async fn remove_static_lease(&self, lease: StaticLease) -> Result<()> {
-        let url = format!("{}/control/dhcp/remove_static_lease", self.config.url);
+        let url = self.endpoint("/control/dhcp/remove_static_lease");
         let request = self.add_auth(self.client.post(&url).json(&lease));
 
         request.send().await?.error_for_status()?;
         Ok(())
     }
 
+    /// Changes the IP or hostname of the static lease with the same MAC.
+    /// Instances without `/control/dhcp/update_static_lease` get a remove and re-add.
+    pub async fn update_static_lease(&self, lease: StaticLease) -> Result<()> {
+        let url = self.endpoint("/control/dhcp/update_static_lease");
+        let request = self.add_auth(self.client.post(&url).json(&lease));
+
+        let response = request.send().await?;
+        if !matches!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::METHOD_NOT_ALLOWED
+        ) {
+            response.error_for_status()?;
+            return Ok(());
+        }
+
+        let status = self.get_dhcp_status().await?;
+        if let Some(existing) = status
+            .static_leases
+            .into_iter()
+            .find(|l| l.mac.eq_ignore_ascii_case(&lease.mac))
+        {
+            self.remove_static_lease(existing.clone()).await?;
+            if let Err(e) = self.add_static_lease(lease).await {
+                // Put the original lease back so a rejected change loses nothing
+                if let Err(restore) = self.add_static_lease(existing).await {
+                    tracing::warn!("Failed to restore static lease: {}", restore);
+                }
+                return Err(e);
+            }
+            return Ok(());
+        }
+        self.add_static_lease(lease).await
+    }
+
     pub async fn get_dns_info(&self) -> Result<DnsConfig> {
-        let url = format!("{}/control/dns_info", self.config.url);
+        let url = self.endpoint("/control/dns_info");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -443,15 +909,29 @@ impl AdGuardClient {
     }
 
     pub async fn set_dns_config(&self, config: DnsConfig) -> Result<()> {
-        let url = format!("{}/control/dns_config", self.config.url);
+        let url = self.endpoint("/control/dns_config");
         let request = self.add_auth(self.client.post(&url).json(&config));
 
         request.send().await?.error_for_status()?;
         Ok(())
     }
 
+    /// Asks AdGuard Home to query each upstream; maps every upstream to "OK"
+    /// or the error it returned.
+    pub async fn test_upstream_dns(
+        &self,
+        upstreams: &UpstreamTestRequest,
+    ) -> Result<HashMap<String, String>> {
+        let url = self.endpoint("/control/test_upstream_dns");
+        let request = self.add_auth(self.client.post(&url).json(upstreams));
+
+        let response = request.send().await?.error_for_status()?;
+        let results = response.json::<HashMap<String, String>>().await?;
+        Ok(results)
+    }
+
     pub async fn clear_dns_cache(&self) -> Result<()> {
-        let url = format!("{}/control/cache_clear", self.config.url);
+        let url = self.endpoint("/control/cache_clear");
         let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
@@ -459,7 +939,7 @@ impl AdGuardClient {
     }
 
     pub async fn get_access_list(&self) -> Result<AccessList> {
-        let url = format!("{}/control/access/list", self.config.url);
+        let url = self.endpoint("/control/access/list");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -468,7 +948,7 @@ impl AdGuardClient {
     }
 
     pub async fn set_access_list(&self, list: AccessList) -> Result<()> {
-        let url = format!("{}/control/access/set", self.config.url);
+        let url = self.endpoint("/control/access/set");
         let request = self.add_auth(self.client.post(&url).json(&list));
 
         request.send().await?.error_for_status()?;
@@ -480,7 +960,7 @@ impl AdGuardClient {
         name: &str,
         client: Option<&str>,
     ) -> Result<FilterCheckResponse> {
-        let mut url = format!("{}/control/filtering/check_host", self.config.url);
+        let mut url = self.endpoint("/control/filtering/check_host");
         url.push_str(&format!("?name={}", name));
         if let Some(c) = client {
             url.push_str(&format!("&client={}", c));
@@ -494,7 +974,7 @@ impl AdGuardClient {
     }
 
     pub async fn reset_stats(&self) -> Result<()> {
-        let url = format!("{}/control/stats_reset", self.config.url);
+        let url = self.endpoint("/control/stats_reset");
         let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
@@ -502,7 +982,7 @@ impl AdGuardClient {
     }
 
     pub async fn clear_query_log(&self) -> Result<()> {
-        let url = format!("{}/control/querylog_clear", self.config.url);
+        let url = self.endpoint("/control/querylog_clear");
         let request = self.add_auth(self.client.post(&url));
 
         request.send().await?.error_for_status()?;
@@ -511,14 +991,14 @@ impl AdGuardClient {
 
     pub async fn restart_service(&self, hard: bool) -> Result<()> {
         if hard {
-            let url = format!("{}/control/restart", self.config.url);
+            let url = self.endpoint("/control/restart");
             let request = self.add_auth(self.client.post(&url));
             // We ignore error_for_status here because /restart often closes the connection
             // before returning a response, causing a "connection closed" error.
             let _ = request.send().await;
         } else {
             // Soft restart (refresh filters)
-            let url = format!("{}/control/filtering/refresh", self.config.url);
+            let url = self.endpoint("/control/filtering/refresh");
             let request = self.add_auth(
                 self.client
                     .post(&url)
@@ -530,7 +1010,7 @@ impl AdGuardClient {
     }
 
     pub async fn get_tls_status(&self) -> Result<TlsConfig> {
-        let url = format!("{}/control/tls/status", self.config.url);
+        let url = self.endpoint("/control/tls/status");
         let request = self.add_auth(self.client.get(&url));
 
         let response = request.send().await?.error_for_status()?;
@@ -539,7 +1019,7 @@ impl AdGuardClient {
     }
 
     pub async fn configure_tls(&self, config: TlsConfig) -> Result<()> {
-        let url = format!("{}/control/tls/configure", self.config.url);
+        let url = self.endpoint("/control/tls/configure");
         let request = self.add_auth(self.client.post(&url).json(&config));
 
         request.send().await?.error_for_status()?;
@@ -547,7 +1027,7 @@ impl AdGuardClient {
     }
 
     pub async fn validate_tls(&self, config: TlsConfig) -> Result<TlsConfig> {
-        let url = format!("{}/control/tls/validate", self.config.url);
+        let url = self.endpoint("/control/tls/validate");
         let request = self.add_auth(self.client.post(&url).json(&config));
 
         let response = request.send().await?.error_for_status()?;
@@ -555,3 +1035,25 @@ impl AdGuardClient {
         Ok(result)
     }
 }
+
+// Legacy query log and stats endpoints express retention in days
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+fn days_to_ms(days: u64) -> u64 {
+    days * MS_PER_DAY
+}
+
+/// Retention values the legacy endpoints accept, in days.
+const LEGACY_RETENTION_DAYS: [u64; 4] = [1, 7, 30, 90];
+
+fn legacy_retention_days(ms: u64, caps: &Capabilities) -> Result<u64> {
+    let days = ms / MS_PER_DAY;
+    caps.require(
+        ms.is_multiple_of(MS_PER_DAY) && LEGACY_RETENTION_DAYS.contains(&days),
+        &format!(
+            "A retention of {} hours (only 1, 7, 30 or 90 days)",
+            ms / (60 * 60 * 1000)
+        ),
+    )?;
+    Ok(days)
+}
@@ -0,0 +1,212 @@
+use super::client::AdGuardClient;
+use super::models::{
+    AdGuardClientDevice, DhcpStatus, QueryLogEntry, QueryLogParams, RuntimeClient,
+};
+use super::oui;
+use crate::error::Result;
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// One physical device as seen across runtime clients, DHCP leases and the query log.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct DiscoveredDevice {
+    pub ips: Vec<String>,
+    pub macs: Vec<String>,
+    pub names: Vec<String>,
+    pub sources: Vec<String>,
+    pub queries: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+    /// Vendor of the first MAC address, from the embedded OUI table.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    pub randomized_mac: bool,
+    /// Name of the persistent client that already covers this device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistent_client: Option<String>,
+}
+
+impl DiscoveredDevice {
+    pub fn matches(&self, identifier: &str) -> bool {
+        let identifier = identifier.to_ascii_lowercase();
+        self.ips.contains(&identifier)
+            || self.macs.contains(&identifier)
+            || self
+                .names
+                .iter()
+                .any(|n| n.eq_ignore_ascii_case(&identifier))
+    }
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !value.is_empty() && !values.iter().any(|v| v == value) {
+        values.push(value.to_string());
+    }
+}
+
+#[derive(Default)]
+struct Inventory {
+    devices: Vec<DiscoveredDevice>,
+    by_key: HashMap<String, usize>,
+}
+
+impl Inventory {
+    fn device(&mut self, ip: &str, mac: &str) -> &mut DiscoveredDevice {
+        let ip = ip.to_ascii_lowercase();
+        let mac = mac.to_ascii_lowercase();
+        let ip_key = format!("ip:{}", ip);
+        let mac_key = format!("mac:{}", mac);
+
+        let idx = [&mac_key, &ip_key]
+            .into_iter()
+            .filter(|k| k.len() > 4)
+            .find_map(|k| self.by_key.get(k).copied())
+            .unwrap_or_else(|| {
+                self.devices.push(DiscoveredDevice::default());
+                self.devices.len() - 1
+            });
+
+        for key in [ip_key, mac_key] {
+            if key.len() > 4 {
+                self.by_key.entry(key).or_insert(idx);
+            }
+        }
+        let device = &mut self.devices[idx];
+        push_unique(&mut device.ips, &ip);
+        push_unique(&mut device.macs, &mac);
+        device
+    }
+
+    fn add_dhcp(&mut self, dhcp: &DhcpStatus) {
+        for lease in &dhcp.static_leases {
+            let device = self.device(&lease.ip, &lease.mac);
+            push_unique(&mut device.names, &lease.hostname);
+            push_unique(&mut device.sources, "DHCP static lease");
+        }
+        for lease in &dhcp.leases {
+            let device = self.device(&lease.ip, &lease.mac);
+            push_unique(&mut device.names, &lease.hostname);
+            push_unique(&mut device.sources, "DHCP lease");
+        }
+    }
+
+    fn add_runtime(&mut self, clients: &[RuntimeClient]) {
+        for runtime in clients {
+            let device = self.device(&runtime.ip, "");
+            push_unique(&mut device.names, &runtime.name);
+            push_unique(&mut device.sources, &runtime.source);
+        }
+    }
+
+    fn add_activity(&mut self, entries: &[QueryLogEntry]) {
+        for entry in entries {
+            let device = self.device(&entry.client, "");
+            device.queries += 1;
+            if let Some(info) = &entry.client_info
+                && info.name != entry.client
+            {
+                push_unique(&mut device.names, &info.name);
+            }
+            push_unique(&mut device.sources, "query log");
+            if device
+                .last_seen
+                .as_deref()
+                .is_none_or(|seen| seen < entry.time.as_str())
+            {
+                device.last_seen = Some(entry.time.clone());
+            }
+        }
+    }
+
+    fn mark_persistent(&mut self, persistent: &[AdGuardClientDevice]) {
+        for device in &mut self.devices {
+            device.persistent_client = persistent
+                .iter()
+                .find(|c| {
+                    c.ids.iter().any(|id| {
+                        let id = id.to_ascii_lowercase();
+                        device.ips.contains(&id) || device.macs.contains(&id)
+                    })
+                })
+                .map(|c| c.name.clone());
+        }
+    }
+}
+
+/// Merges runtime clients, DHCP leases and recent query log activity into one
+/// de-duplicated inventory, keyed by IP and MAC.
+pub fn merge_inventory(
+    persistent: &[AdGuardClientDevice],
+    runtime: &[RuntimeClient],
+    dhcp: Option<&DhcpStatus>,
+    activity: &[QueryLogEntry],
+) -> Vec<DiscoveredDevice> {
+    let mut inventory = Inventory::default();
+    // DHCP first so MAC addresses anchor devices before IP-only sources merge in
+    if let Some(dhcp) = dhcp {
+        inventory.add_dhcp(dhcp);
+    }
+    inventory.add_runtime(runtime);
+    inventory.add_activity(activity);
+    inventory.mark_persistent(persistent);
+
+    let mut devices = inventory.devices;
+    for device in &mut devices {
+        if let Some(info) = device.macs.first().and_then(|mac| oui::lookup(mac)) {
+            device.vendor = info.vendor.map(|v| v.to_string());
+            device.randomized_mac = info.randomized;
+        }
+    }
+    devices.sort_by(|a, b| b.queries.cmp(&a.queries).then_with(|| a.ips.cmp(&b.ips)));
+    devices
+}
+
+impl AdGuardClient {
+    /// Builds the device inventory from this instance, scanning up to
+    /// `log_limit` recent query log entries for activity.
+    pub async fn discover_clients(&self, log_limit: usize) -> Result<Vec<DiscoveredDevice>> {
+        let clients = self.get_clients().await?;
+
+        // DHCP and the query log may be disabled; discovery still works without them
+        let dhcp = match self.get_dhcp_status().await {
+            Ok(dhcp) => Some(dhcp),
+            Err(e) => {
+                tracing::debug!("DHCP status unavailable for discovery: {}", e);
+                None
+            }
+        };
+
+        let mut activity = Vec::new();
+        if log_limit > 0 {
+            let params = QueryLogParams {
+                limit: Some(log_limit.min(500) as u32),
+                ..Default::default()
+            };
+            let mut pages = std::pin::pin!(self.query_log_pages(params, None));
+            loop {
+                match pages.try_next().await {
+                    Ok(Some(page)) => {
+                        activity.extend(page);
+                        if activity.len() >= log_limit {
+                            activity.truncate(log_limit);
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::debug!("Query log unavailable for discovery: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(merge_inventory(
+            &clients.clients,
+            &clients.auto_clients,
+            dhcp.as_ref(),
+            &activity,
+        ))
+    }
+}
@@ -0,0 +1,123 @@
+use super::client::AdGuardClient;
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Lists larger than this are rejected by the preview rather than buffered.
+const MAX_PREVIEW_BYTES: usize = 50 * 1024 * 1024;
+/// Bounds the whole download, so a list host that stalls mid-body cannot hang the tool.
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(60);
+const PREVIEW_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const SAMPLE_RULES: usize = 10;
+
+/// Rule count and syntax breakdown of a downloaded filter list.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct FilterPreview {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub bytes: usize,
+    pub rules: usize,
+    pub comments: usize,
+    pub adblock: usize,
+    pub exceptions: usize,
+    pub hosts: usize,
+    pub domains: usize,
+    pub regex: usize,
+    /// Element hiding and other browser-only rules that AdGuard Home ignores.
+    pub cosmetic: usize,
+    pub other: usize,
+    pub samples: Vec<String>,
+}
+
+fn is_domain(text: &str) -> bool {
+    text.contains('.')
+        && text.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+impl FilterPreview {
+    pub fn analyze(text: &str) -> Self {
+        let mut preview = Self {
+            bytes: text.len(),
+            ..Default::default()
+        };
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let cosmetic = ["##", "#@#", "#?#", "#$#", "#%#"]
+                .iter()
+                .any(|m| line.contains(m));
+            if !cosmetic && (line.starts_with('!') || line.starts_with('#')) {
+                preview.comments += 1;
+                if preview.title.is_none()
+                    && let Some(title) = line
+                        .trim_start_matches(['!', '#'])
+                        .trim()
+                        .strip_prefix("Title:")
+                {
+                    preview.title = Some(title.trim().to_string());
+                }
+                continue;
+            }
+
+            preview.rules += 1;
+            if preview.samples.len() < SAMPLE_RULES {
+                preview.samples.push(line.to_string());
+            }
+
+            let mut parts = line.split_whitespace();
+            let first = parts.next().unwrap_or_default();
+            if cosmetic {
+                preview.cosmetic += 1;
+            } else if line.starts_with("@@") {
+                preview.exceptions += 1;
+            } else if line.len() > 1 && line.starts_with('/') && line.ends_with('/') {
+                preview.regex += 1;
+            } else if first.parse::<IpAddr>().is_ok() && parts.next().is_some() {
+                preview.hosts += 1;
+            } else if is_domain(line) {
+                preview.domains += 1;
+            } else if line.starts_with("||")
+                || line.starts_with('|')
+                || line.contains('^')
+                || line.contains('$')
+            {
+                preview.adblock += 1;
+            } else {
+                preview.other += 1;
+            }
+        }
+        preview
+    }
+}
+
+impl AdGuardClient {
+    /// Downloads a filter list without adding it to the instance. Only the
+    /// instance's proxy is used: its auth headers are not sent to the list host.
+    pub async fn preview_filter(&self, url: &str) -> Result<FilterPreview> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(PREVIEW_TIMEOUT)
+            .connect_timeout(PREVIEW_CONNECT_TIMEOUT);
+        if let Some(proxy) = &self.config.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        let mut response = builder.build()?.get(url).send().await?.error_for_status()?;
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > MAX_PREVIEW_BYTES {
+                return Err(Error::Generic(format!(
+                    "Filter list exceeds {} MB",
+                    MAX_PREVIEW_BYTES / 1024 / 1024
+                )));
+            }
+        }
+
+        Ok(FilterPreview::analyze(&String::from_utf8_lossy(&body)))
+    }
+}
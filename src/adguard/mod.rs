@@ -1,8 +1,21 @@
+pub mod capabilities;
 pub mod client;
+pub mod discovery;
+pub mod filter_list;
 pub mod models;
+pub mod oui;
+pub mod rewrite_analysis;
+pub mod rewrites;
+pub mod schedule;
 
-pub use client::AdGuardClient;
+pub use capabilities::Capabilities;
+pub use client::{AdGuardClient, ClientCache};
+pub use discovery::DiscoveredDevice;
+pub use filter_list::FilterPreview;
 pub use models::*;
+pub use rewrite_analysis::{RewriteAnalysis, RewriteResolution};
+pub use rewrites::{RewriteDiff, RewriteFormat};
+pub use schedule::{DayRange, WeeklySchedule};
 
 #[cfg(test)]
 mod tests;
@@ -1,3 +1,4 @@
+use super::schedule::WeeklySchedule;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -8,10 +9,37 @@ pub struct Status {
     pub protection_enabled: bool,
 }
 
+/// Body of `/control/test_upstream_dns`.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct UpstreamTestRequest {
+    pub upstream_dns: Vec<String>,
+    pub bootstrap_dns: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fallback_dns: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DnsRewrite {
     pub domain: String,
     pub answer: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// Rewrites are identified by domain and answer alone; the server matches them
+/// the same way.
+impl PartialEq for DnsRewrite {
+    fn eq(&self, other: &Self) -> bool {
+        self.domain == other.domain && self.answer == other.answer
+    }
+}
+
+impl Eq for DnsRewrite {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateRewriteRequest {
+    pub target: DnsRewrite,
+    pub update: DnsRewrite,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +56,33 @@ pub struct Stats {
     pub top_blocked_domains: Vec<HashMap<String, u64>>,
     #[serde(default)]
     pub top_clients: Vec<HashMap<String, u64>>,
+    #[serde(default)]
+    pub top_upstreams_responses: Vec<HashMap<String, u64>>,
+    /// Average upstream response time in seconds.
+    #[serde(default)]
+    pub top_upstreams_avg_time: Vec<HashMap<String, f64>>,
+    /// Unit of each time series bucket: "hours" or "days".
+    #[serde(default)]
+    pub time_units: String,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub dns_queries: Vec<u64>,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub blocked_filtering: Vec<u64>,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub replaced_safebrowsing: Vec<u64>,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub replaced_parental: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StatsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub interval: u64, // retention interval in milliseconds; legacy APIs are converted by the client
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub ignored: Vec<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,6 +93,56 @@ pub struct QueryLogEntry {
     pub status: String,
     pub time: String,
     pub question: QueryLogQuestion,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub answer: Vec<QueryLogAnswer>,
+    /// Answer before a rewrite or safe search replaced it.
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub original_answer: Vec<QueryLogAnswer>,
+    #[serde(default)]
+    pub upstream: String,
+    #[serde(default)]
+    pub cached: bool,
+    /// Transport the client used: empty for plain DNS, or "doh", "dot", "doq", "dnscrypt".
+    #[serde(default)]
+    pub client_proto: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_info: Option<QueryLogClientInfo>,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub rules: Vec<QueryLogRule>,
+    #[serde(default)]
+    pub service_name: String,
+    #[serde(default)]
+    pub answer_dnssec: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryLogAnswer {
+    #[serde(rename = "type", default)]
+    pub rtype: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default)]
+    pub ttl: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct QueryLogClientInfo {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub whois: HashMap<String, String>,
+    #[serde(default)]
+    pub disallowed: bool,
+    #[serde(default)]
+    pub disallowed_rule: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryLogRule {
+    #[serde(default)]
+    pub filter_list_id: i64,
+    #[serde(default)]
+    pub text: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,6 +156,40 @@ pub struct QueryLogQuestion {
 pub struct QueryLogResponse {
     #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub data: Vec<QueryLogEntry>,
+    /// Time of the oldest entry in `data`; pass it as `older_than` to fetch the next page.
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub oldest: String,
+}
+
+/// `response_status` values accepted by `/control/querylog`.
+pub const QUERY_LOG_RESPONSE_STATUSES: &[&str] = &[
+    "all",
+    "filtered",
+    "blocked",
+    "blocked_safebrowsing",
+    "blocked_parental",
+    "whitelisted",
+    "rewritten",
+    "safe_search",
+    "processed",
+];
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct QueryLogParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<String>,
+    /// RFC 3339 cursor: only entries strictly older than this are returned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Exact client IP or name; applied to the returned entries since the API has no such parameter.
+    #[serde(skip)]
+    pub client: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -61,6 +200,12 @@ pub struct Filter {
     pub enabled: bool,
     pub last_updated: Option<String>,
     pub rules_count: u32,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 fn deserialize_null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
@@ -72,7 +217,7 @@ where
     Ok(opt.unwrap_or_default())
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct FilteringConfig {
     pub enabled: bool,
     pub interval: u32,
@@ -82,6 +227,14 @@ pub struct FilteringConfig {
     pub whitelist_filters: Vec<Filter>,
     #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub user_rules: Vec<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct RefreshFiltersResponse {
+    #[serde(default)]
+    pub updated: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -130,7 +283,7 @@ pub struct UpdateFilterData {
     pub enabled: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct AdGuardClientDevice {
     pub name: String,
     #[serde(default, deserialize_with = "deserialize_null_as_default")]
@@ -140,12 +293,62 @@ pub struct AdGuardClientDevice {
     pub parental_enabled: bool,
     pub safebrowsing_enabled: bool,
     pub safesearch_enabled: bool,
+    // Optional so settings an older instance does not report are never sent back as zero values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safe_search: Option<SafeSearchConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_global_blocked_services: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_services: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_services_schedule: Option<WeeklySchedule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstreams: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstreams_cache_enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstreams_cache_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_querylog: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_statistics: Option<bool>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl AdGuardClientDevice {
+    /// Copy without unknown and server-populated fields, for comparing the
+    /// settings of the same client on two instances.
+    pub fn settings(&self) -> Self {
+        Self {
+            extra: HashMap::new(),
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClientsResponse {
     #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub clients: Vec<AdGuardClientDevice>,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub supported_tags: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub auto_clients: Vec<RuntimeClient>,
+}
+
+/// Client AdGuard Home knows about without a persistent entry (ARP, rDNS, DHCP, WHOIS, hosts).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RuntimeClient {
+    pub ip: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub whois_info: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -161,6 +364,16 @@ pub struct BlockedServicesAllResponse {
     pub services: Vec<BlockedService>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BlockedServicesConfig {
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<WeeklySchedule>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SetBlockedServicesRequest {
     pub ids: Vec<String>,
@@ -222,9 +435,46 @@ pub struct DnsConfig {
     pub use_private_ptr_resolvers: bool,
     #[serde(default)]
     pub local_ptr_upstreams: Vec<String>,
+    // Optional so that settings an instance doesn't report are never sent back as zero values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolve_clients: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_timeout: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocking_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocking_ipv4: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocking_ipv6: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_response_ttl: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratelimit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratelimit_subnet_len_ipv4: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratelimit_subnet_len_ipv6: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratelimit_whitelist: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnssec_enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edns_cs_enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edns_cs_use_custom: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edns_cs_custom_ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_ipv6: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refuse_any: Option<bool>,
+    // Fields not modelled above are kept and sent back unchanged, so
+    // read-modify-write never resets settings on the instance
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DhcpStatus {
     pub enabled: bool,
     pub interface_name: String,
@@ -234,28 +484,113 @@ pub struct DhcpStatus {
     pub leases: Vec<DhcpLease>,
     #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub static_leases: Vec<StaticLease>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// Body of `/control/dhcp/set_config`: the configuration part of `DhcpStatus` without leases.
+#[derive(Debug, Serialize, Clone)]
+pub struct DhcpConfig {
+    pub enabled: bool,
+    pub interface_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v4: Option<DhcpConfigV4>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v6: Option<DhcpConfigV6>,
+}
+
+impl From<&DhcpStatus> for DhcpConfig {
+    fn from(status: &DhcpStatus) -> Self {
+        Self {
+            enabled: status.enabled,
+            interface_name: status.interface_name.clone(),
+            v4: status.v4.clone(),
+            v6: status.v6.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetInterface {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub hardware_address: String,
+    #[serde(default)]
+    pub flags: String,
+    #[serde(default)]
+    pub gateway_ip: String,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub ipv4_addresses: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub ipv6_addresses: Vec<String>,
+}
+
+/// Result of `/control/dhcp/find_active_dhcp`; `found`/`static` are "yes", "no" or "error".
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DhcpSearchResult {
+    #[serde(default)]
+    pub v4: DhcpSearchV4,
+    #[serde(default)]
+    pub v6: DhcpSearchV6,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DhcpSearchV4 {
+    #[serde(default)]
+    pub other_server: DhcpOtherServer,
+    #[serde(default)]
+    pub static_ip: DhcpStaticIp,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DhcpSearchV6 {
+    #[serde(default)]
+    pub other_server: DhcpOtherServer,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DhcpOtherServer {
+    #[serde(default)]
+    pub found: String,
+    #[serde(default)]
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DhcpStaticIp {
+    #[serde(rename = "static", default)]
+    pub is_static: String,
+    #[serde(default)]
+    pub ip: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DhcpConfigV4 {
     pub gateway_ip: Option<String>,
     pub subnet_mask: Option<String>,
     pub range_start: Option<String>,
     pub range_end: Option<String>,
     pub lease_duration: Option<u32>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DhcpConfigV6 {
     pub range_start: Option<String>,
     pub lease_duration: Option<u32>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ProfileInfo {
     pub name: String,
     pub language: String,
     pub theme: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -266,6 +601,8 @@ pub struct AccessList {
     pub disallowed_clients: Vec<String>,
     #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub blocked_hosts: Vec<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -284,7 +621,7 @@ pub struct FilterCheckMatchedRule {
     pub text: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SafeSearchConfig {
     pub enabled: bool,
     pub bing: bool,
@@ -293,23 +630,29 @@ pub struct SafeSearchConfig {
     pub pixabay: bool,
     pub yandex: bool,
     pub youtube: bool,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ParentalControlConfig {
     pub enabled: bool,
     pub sensitivity: Option<u32>, // Optional, as it might not be present in all versions or configs
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct QueryLogConfig {
     pub enabled: bool,
-    pub interval: u64, // retention interval in milliseconds (v0.107+) or hours (older)
+    pub interval: u64, // retention interval in milliseconds; legacy APIs are converted by the client
     pub anonymize_client_ip: bool,
     #[serde(default)]
     pub allowed_clients: Vec<String>,
     #[serde(default)]
     pub disallowed_clients: Vec<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -344,4 +687,51 @@ pub struct TlsConfig {
     pub valid_key: bool,
     #[serde(default)]
     pub valid_pair: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_chain: Option<bool>,
+    /// Set when AdGuard Home keeps a saved key it does not return.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_saved: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// RFC 3339 start of the certificate's validity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<String>,
+    /// RFC 3339 end of the certificate's validity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_null_as_default",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub dns_names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_type: Option<String>,
+    /// Problem found by AdGuard Home when checking the certificate and key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning_validation: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+pub const REDACTED: &str = "[REDACTED]";
+
+impl TlsConfig {
+    /// Copy safe to show: the private key is replaced, its path is kept.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if !config.private_key.is_empty() {
+            config.private_key = REDACTED.to_string();
+        }
+        config
+    }
+
+    /// Whole days until `not_after`, negative once expired.
+    pub fn days_until_expiry(&self, now: chrono::DateTime<chrono::Utc>) -> Option<i64> {
+        let not_after = chrono::DateTime::parse_from_rfc3339(self.not_after.as_deref()?).ok()?;
+        Some((not_after.with_timezone(&chrono::Utc) - now).num_days())
+    }
 }
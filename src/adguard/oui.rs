@@ -0,0 +1,64 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+static OUI_TABLE: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
+    include_str!("oui.txt")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .collect()
+});
+
+/// What the first three octets of a MAC address reveal about the device.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct MacVendor {
+    pub vendor: Option<&'static str>,
+    /// The U/L bit is set: the address was not assigned by the manufacturer.
+    pub locally_administered: bool,
+    /// Locally administered and not a known virtual NIC prefix, typically a
+    /// phone or laptop using a private (randomized) Wi-Fi address.
+    pub randomized: bool,
+}
+
+impl MacVendor {
+    pub fn label(&self) -> String {
+        match (self.vendor, self.randomized) {
+            (Some(vendor), _) => vendor.to_string(),
+            (None, true) => "randomized/private address".to_string(),
+            (None, false) => "unknown vendor".to_string(),
+        }
+    }
+}
+
+fn mac_digits(mac: &str) -> Option<String> {
+    let digits: String = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    (digits.len() == 12 && digits.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| digits.to_ascii_uppercase())
+}
+
+/// Looks up the vendor of a MAC address; `None` if `mac` is not a MAC address.
+pub fn lookup(mac: &str) -> Option<MacVendor> {
+    let digits = mac_digits(mac)?;
+    let first_octet = u8::from_str_radix(&digits[..2], 16).ok()?;
+    let vendor = OUI_TABLE.get(&digits[..6]).copied();
+    let locally_administered = first_octet & 0x02 != 0;
+
+    Some(MacVendor {
+        vendor,
+        locally_administered,
+        randomized: locally_administered && vendor.is_none(),
+    })
+}
+
+/// Formats a MAC as "aa:bb:cc:dd:ee:ff (Vendor)", or returns it unchanged if
+/// it is not a MAC address.
+pub fn annotate(mac: &str) -> String {
+    match lookup(mac) {
+        Some(info) => format!("{} ({})", mac, info.label()),
+        None => mac.to_string(),
+    }
+}
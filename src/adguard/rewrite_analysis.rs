@@ -0,0 +1,397 @@
+use super::client::AdGuardClient;
+use super::models::{AdGuardClientDevice, DhcpStatus, DnsRewrite};
+use crate::error::Result;
+use serde::Serialize;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Answers AdGuard Home treats as "keep the upstream records of this type".
+const PASSTHROUGH_ANSWERS: [&str; 2] = ["A", "AAAA"];
+const MAX_CNAME_DEPTH: usize = 16;
+
+fn is_cname(answer: &str) -> bool {
+    answer.parse::<IpAddr>().is_err() && !PASSTHROUGH_ANSWERS.contains(&answer)
+}
+
+/// Rewrites that apply to `name`, following AdGuard Home's precedence: exact
+/// domains first, then the most specific matching wildcard.
+pub fn matching_rewrites<'a>(rewrites: &'a [DnsRewrite], name: &str) -> Vec<&'a DnsRewrite> {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    let exact: Vec<_> = rewrites
+        .iter()
+        .filter(|r| r.domain.eq_ignore_ascii_case(&name))
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+
+    let best = rewrites
+        .iter()
+        .filter_map(|r| r.domain.strip_prefix("*.").map(|base| (r, base)))
+        .filter(|(_, base)| name.ends_with(&format!(".{}", base.to_ascii_lowercase())))
+        .map(|(_, base)| base.len())
+        .max();
+    match best {
+        Some(len) => rewrites
+            .iter()
+            .filter(|r| {
+                r.domain.strip_prefix("*.").is_some_and(|base| {
+                    base.len() == len && name.ends_with(&format!(".{}", base.to_ascii_lowercase()))
+                })
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Which rewrites answer a name, with CNAME answers followed through other rewrites.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct RewriteResolution {
+    pub name: String,
+    /// Winning rewrite at each step of the CNAME chain.
+    pub chain: Vec<DnsRewrite>,
+    /// Final answers; empty when nothing matches and the name goes upstream.
+    pub answers: Vec<String>,
+    pub looped: bool,
+}
+
+pub fn resolve(rewrites: &[DnsRewrite], name: &str) -> RewriteResolution {
+    let mut resolution = RewriteResolution {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    let mut visited = vec![name.trim_end_matches('.').to_ascii_lowercase()];
+    let mut current = visited[0].clone();
+
+    loop {
+        let matches = matching_rewrites(rewrites, &current);
+        let Some(cname) = matches.iter().find(|r| is_cname(&r.answer)) else {
+            resolution
+                .chain
+                .extend(matches.iter().map(|r| (*r).clone()));
+            resolution.answers = matches.iter().map(|r| r.answer.clone()).collect();
+            return resolution;
+        };
+
+        resolution.chain.push((*cname).clone());
+        let target = cname.answer.trim_end_matches('.').to_ascii_lowercase();
+        if visited.contains(&target) || visited.len() > MAX_CNAME_DEPTH {
+            resolution.looped = true;
+            return resolution;
+        }
+        if matching_rewrites(rewrites, &target).is_empty() {
+            // Target is resolved upstream
+            resolution.answers = vec![cname.answer.clone()];
+            return resolution;
+        }
+        visited.push(target.clone());
+        current = target;
+    }
+}
+
+/// A wildcard rewrite that does not apply to a name because a more specific rule wins.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WildcardOverlap {
+    pub wildcard: DnsRewrite,
+    pub winner: DnsRewrite,
+    /// Both give the same answer, so the more specific rule is redundant.
+    pub same_answer: bool,
+}
+
+/// A rewrite for a name DHCP already answers for.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DhcpOverlap {
+    pub rewrite: DnsRewrite,
+    pub hostname: String,
+    pub lease_ip: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct RewriteAnalysis {
+    pub total: usize,
+    /// Rewrites listed more than once with the same answer.
+    pub duplicates: Vec<DnsRewrite>,
+    /// Domains mixing a CNAME with other answers; only the CNAME is used.
+    pub conflicts: Vec<String>,
+    pub wildcard_overlaps: Vec<WildcardOverlap>,
+    pub cname_loops: Vec<Vec<String>>,
+    pub dhcp_overlaps: Vec<DhcpOverlap>,
+    /// Private addresses outside every subnet known from DHCP and client CIDRs.
+    pub stale_answers: Vec<DnsRewrite>,
+}
+
+impl RewriteAnalysis {
+    pub fn is_clean(&self) -> bool {
+        self.duplicates.is_empty()
+            && self.conflicts.is_empty()
+            && self.wildcard_overlaps.is_empty()
+            && self.cname_loops.is_empty()
+            && self.dhcp_overlaps.is_empty()
+            && self.stale_answers.is_empty()
+    }
+}
+
+fn parse_network(cidr: &str) -> Option<(IpAddr, u32)> {
+    let (ip, len) = cidr.split_once('/')?;
+    Some((ip.parse().ok()?, len.parse().ok()?))
+}
+
+fn in_network(ip: IpAddr, (network, len): (IpAddr, u32)) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) if len <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - len).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) if len <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_link_local(),
+        // fc00::/7 unique local addresses
+        IpAddr::V6(v6) => v6.segments()[0] & 0xfe00 == 0xfc00,
+    }
+}
+
+fn known_networks(
+    dhcp: Option<&DhcpStatus>,
+    clients: &[AdGuardClientDevice],
+) -> Vec<(IpAddr, u32)> {
+    let mut networks: Vec<_> = clients
+        .iter()
+        .flat_map(|c| &c.ids)
+        .filter_map(|id| parse_network(id))
+        .collect();
+    if let Some(v4) = dhcp.and_then(|d| d.v4.as_ref())
+        && let (Some(gateway), Some(mask)) = (
+            v4.gateway_ip.as_deref().and_then(|ip| ip.parse().ok()),
+            v4.subnet_mask
+                .as_deref()
+                .and_then(|m| m.parse::<std::net::Ipv4Addr>().ok()),
+        )
+    {
+        networks.push((gateway, u32::from(mask).count_ones()));
+    }
+    networks
+}
+
+/// Checks rewrites for duplicates, CNAME conflicts and loops, wildcards that
+/// never apply to some names, overlap with DHCP hostnames and stale private answers.
+pub fn analyze_rewrites(
+    rewrites: &[DnsRewrite],
+    dhcp: Option<&DhcpStatus>,
+    clients: &[AdGuardClientDevice],
+) -> RewriteAnalysis {
+    let mut analysis = RewriteAnalysis {
+        total: rewrites.len(),
+        ..Default::default()
+    };
+
+    for (i, rewrite) in rewrites.iter().enumerate() {
+        if rewrites[..i].contains(rewrite) && !analysis.duplicates.contains(rewrite) {
+            analysis.duplicates.push(rewrite.clone());
+        }
+    }
+
+    let mut domains: Vec<&str> = rewrites.iter().map(|r| r.domain.as_str()).collect();
+    domains.sort_unstable();
+    domains.dedup();
+    for domain in &domains {
+        let mut answers: Vec<&str> = rewrites
+            .iter()
+            .filter(|r| r.domain == *domain)
+            .map(|r| r.answer.as_str())
+            .collect();
+        answers.sort_unstable();
+        answers.dedup();
+        let cnames = answers.iter().filter(|a| is_cname(a)).count();
+        if cnames > 0 && answers.len() > 1 {
+            analysis.conflicts.push(domain.to_string());
+        }
+    }
+
+    for wildcard in rewrites.iter().filter(|r| r.domain.starts_with("*.")) {
+        let base = &wildcard.domain[2..];
+        for winner in rewrites.iter().filter(|r| {
+            r.domain != wildcard.domain
+                && r.domain
+                    .trim_start_matches("*.")
+                    .ends_with(&format!(".{}", base))
+        }) {
+            analysis.wildcard_overlaps.push(WildcardOverlap {
+                wildcard: wildcard.clone(),
+                winner: winner.clone(),
+                same_answer: winner.answer == wildcard.answer,
+            });
+        }
+    }
+
+    for domain in domains.iter().filter(|d| !d.starts_with("*.")) {
+        let resolution = resolve(rewrites, domain);
+        if resolution.looped
+            && let Some(last) = resolution.chain.last()
+        {
+            let names: Vec<String> = resolution.chain.iter().map(|r| r.domain.clone()).collect();
+            let repeated = last.answer.trim_end_matches('.').to_ascii_lowercase();
+            let start = names.iter().position(|n| *n == repeated).unwrap_or(0);
+            // Rotate so each loop is reported once, from its alphabetically first name
+            let mut cycle = names[start..].to_vec();
+            let first = cycle
+                .iter()
+                .enumerate()
+                .min_by(|a, b| a.1.cmp(b.1))
+                .map_or(0, |(i, _)| i);
+            cycle.rotate_left(first);
+            cycle.push(cycle[0].clone());
+            if !analysis.cname_loops.contains(&cycle) {
+                analysis.cname_loops.push(cycle);
+            }
+        }
+    }
+
+    if let Some(dhcp) = dhcp {
+        let leases = dhcp
+            .static_leases
+            .iter()
+            .map(|l| (&l.hostname, &l.ip))
+            .chain(dhcp.leases.iter().map(|l| (&l.hostname, &l.ip)));
+        for (hostname, ip) in leases.filter(|(h, _)| !h.is_empty()) {
+            for rewrite in rewrites.iter().filter(|r| {
+                r.domain.eq_ignore_ascii_case(hostname)
+                    || r.domain
+                        .split_once('.')
+                        .is_some_and(|(host, _)| host.eq_ignore_ascii_case(hostname))
+            }) {
+                analysis.dhcp_overlaps.push(DhcpOverlap {
+                    rewrite: rewrite.clone(),
+                    hostname: hostname.clone(),
+                    lease_ip: ip.clone(),
+                });
+            }
+        }
+    }
+
+    let networks = known_networks(dhcp, clients);
+    if !networks.is_empty() {
+        analysis.stale_answers = rewrites
+            .iter()
+            .filter(|r| {
+                r.answer.parse::<IpAddr>().is_ok_and(|ip| {
+                    is_private(ip) && !networks.iter().any(|net| in_network(ip, *net))
+                })
+            })
+            .cloned()
+            .collect();
+    }
+    analysis
+}
+
+impl fmt::Display for RewriteAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Analyzed {} rewrites", self.total)?;
+        if self.is_clean() {
+            return writeln!(f, "No problems found.");
+        }
+        if !self.duplicates.is_empty() {
+            writeln!(f, "Duplicates:")?;
+            for r in &self.duplicates {
+                writeln!(f, "- {} -> {}", r.domain, r.answer)?;
+            }
+        }
+        if !self.conflicts.is_empty() {
+            writeln!(f, "Conflicting answers (CNAME mixed with other answers):")?;
+            for domain in &self.conflicts {
+                writeln!(f, "- {}", domain)?;
+            }
+        }
+        if !self.wildcard_overlaps.is_empty() {
+            writeln!(f, "Wildcard overlaps (more specific rule wins):")?;
+            for o in &self.wildcard_overlaps {
+                writeln!(
+                    f,
+                    "- {} -> {} is overridden by {} -> {}{}",
+                    o.wildcard.domain,
+                    o.wildcard.answer,
+                    o.winner.domain,
+                    o.winner.answer,
+                    if o.same_answer {
+                        " (same answer, redundant)"
+                    } else {
+                        ""
+                    }
+                )?;
+            }
+        }
+        if !self.cname_loops.is_empty() {
+            writeln!(f, "CNAME loops:")?;
+            for cycle in &self.cname_loops {
+                writeln!(f, "- {}", cycle.join(" -> "))?;
+            }
+        }
+        if !self.dhcp_overlaps.is_empty() {
+            writeln!(f, "Rewrites duplicating DHCP hostnames:")?;
+            for o in &self.dhcp_overlaps {
+                let note = if o.rewrite.answer == o.lease_ip {
+                    "redundant"
+                } else {
+                    "differs from lease"
+                };
+                writeln!(
+                    f,
+                    "- {} -> {} (lease {} has {}, {})",
+                    o.rewrite.domain, o.rewrite.answer, o.hostname, o.lease_ip, note
+                )?;
+            }
+        }
+        if !self.stale_answers.is_empty() {
+            writeln!(f, "Answers outside every known subnet:")?;
+            for r in &self.stale_answers {
+                writeln!(f, "- {} -> {}", r.domain, r.answer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RewriteResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.chain.is_empty() {
+            return writeln!(
+                f,
+                "No rewrite matches {}; it is resolved upstream.",
+                self.name
+            );
+        }
+        writeln!(f, "{}:", self.name)?;
+        for r in &self.chain {
+            writeln!(f, "- rule {} -> {}", r.domain, r.answer)?;
+        }
+        if self.looped {
+            writeln!(f, "CNAME loop: the name cannot be resolved.")
+        } else {
+            writeln!(f, "Answer: {}", self.answers.join(", "))
+        }
+    }
+}
+
+impl AdGuardClient {
+    /// Analyzes this instance's rewrites against its DHCP leases and clients.
+    pub async fn analyze_rewrites(&self) -> Result<RewriteAnalysis> {
+        let rewrites = self.list_rewrites().await?;
+        let clients = self.list_clients().await?;
+        // DHCP may be disabled; the analysis still works without it
+        let dhcp = match self.get_dhcp_status().await {
+            Ok(dhcp) => Some(dhcp),
+            Err(e) => {
+                tracing::debug!("DHCP status unavailable for rewrite analysis: {}", e);
+                None
+            }
+        };
+        Ok(analyze_rewrites(&rewrites, dhcp.as_ref(), &clients))
+    }
+}
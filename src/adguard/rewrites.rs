@@ -0,0 +1,296 @@
+use super::models::DnsRewrite;
+use serde::Serialize;
+use std::net::IpAddr;
+
+/// Text formats DNS rewrites can be imported from and exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewriteFormat {
+    /// `/etc/hosts` style: `192.168.1.10 nas.lan nas`.
+    Hosts,
+    /// dnsmasq `address=/domain/ip`, `host-record=name,ip` and `cname=alias,target`.
+    Dnsmasq,
+    /// `[{"domain": "...", "answer": "..."}]`, as returned by `list_rewrites`.
+    Json,
+}
+
+impl RewriteFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hosts" => Some(Self::Hosts),
+            "dnsmasq" => Some(Self::Dnsmasq),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn parse(self, text: &str) -> Result<Vec<DnsRewrite>, String> {
+        self.parse_with_warnings(text).map(|(rewrites, _)| rewrites)
+    }
+
+    /// Like `parse`, also returning a warning for each line that was skipped
+    /// because it has no rewrite equivalent.
+    pub fn parse_with_warnings(self, text: &str) -> Result<(Vec<DnsRewrite>, Vec<String>), String> {
+        let mut rewrites = Vec::new();
+        let mut warnings = Vec::new();
+        match self {
+            Self::Json => {
+                rewrites = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?
+            }
+            Self::Hosts => {
+                for (n, line) in content_lines(text, true) {
+                    let mut fields = line.split_whitespace();
+                    let ip = fields.next().unwrap_or_default();
+                    if ip.parse::<IpAddr>().is_err() {
+                        return Err(format!("line {}: '{}' is not an IP address", n, ip));
+                    }
+                    for name in fields.filter(|name| !is_loopback_name(name)) {
+                        push_rewrite(&mut rewrites, name, ip);
+                    }
+                }
+            }
+            Self::Dnsmasq => {
+                // `#` only starts a comment at the beginning of a dnsmasq line
+                for (n, line) in content_lines(text, false) {
+                    let (key, value) = line.split_once('=').unwrap_or((line, ""));
+                    match key.trim() {
+                        "address" => {
+                            let parts: Vec<&str> =
+                                value.trim().trim_start_matches('/').split('/').collect();
+                            let Some((answer, domains)) = parts
+                                .split_last()
+                                .filter(|(_, domains)| !domains.is_empty())
+                            else {
+                                return Err(format!("line {}: expected address=/domain/ip", n));
+                            };
+                            // `#` or no address makes dnsmasq block the domain
+                            if answer.is_empty() || *answer == "#" {
+                                warnings.push(format!(
+                                    "line {}: blocking entry, no rewrite equivalent (use a filtering rule instead)",
+                                    n
+                                ));
+                                continue;
+                            }
+                            if answer.parse::<IpAddr>().is_err() {
+                                return Err(format!(
+                                    "line {}: '{}' is not an IP address",
+                                    n, answer
+                                ));
+                            }
+                            // dnsmasq addresses also answer for every subdomain
+                            for domain in domains.iter().filter(|d| !d.is_empty()) {
+                                push_rewrite(&mut rewrites, domain, answer);
+                                push_rewrite(&mut rewrites, &format!("*.{}", domain), answer);
+                            }
+                        }
+                        "host-record" => {
+                            let fields: Vec<&str> = value.split(',').map(str::trim).collect();
+                            let (ips, names): (Vec<&str>, Vec<&str>) = fields
+                                .iter()
+                                .filter(|f| !f.is_empty() && f.parse::<u32>().is_err())
+                                .partition(|f| f.parse::<IpAddr>().is_ok());
+                            if ips.is_empty() || names.is_empty() {
+                                return Err(format!("line {}: expected host-record=name,ip", n));
+                            }
+                            for name in &names {
+                                for ip in &ips {
+                                    push_rewrite(&mut rewrites, name, ip);
+                                }
+                            }
+                        }
+                        "cname" => {
+                            let fields: Vec<&str> = value.split(',').map(str::trim).collect();
+                            // An optional TTL may follow the target
+                            let fields = match fields.last() {
+                                Some(ttl) if ttl.parse::<u32>().is_ok() => {
+                                    &fields[..fields.len() - 1]
+                                }
+                                _ => &fields[..],
+                            };
+                            let Some((target, aliases)) =
+                                fields.split_last().filter(|(_, a)| !a.is_empty())
+                            else {
+                                return Err(format!("line {}: expected cname=alias,target", n));
+                            };
+                            for alias in aliases {
+                                push_rewrite(&mut rewrites, alias, target);
+                            }
+                        }
+                        // Other dnsmasq options have no rewrite equivalent
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for rewrite in &rewrites {
+            if rewrite.domain.is_empty() || rewrite.answer.is_empty() {
+                return Err("Rewrites need both a domain and an answer".to_string());
+            }
+        }
+        Ok((rewrites, warnings))
+    }
+
+    /// Renders rewrites in this format. Rewrites the format cannot express are
+    /// written as comments so nothing is dropped silently.
+    pub fn format(self, rewrites: &[DnsRewrite]) -> String {
+        let mut out = String::new();
+        match self {
+            Self::Json => {
+                out = serde_json::to_string_pretty(rewrites).unwrap_or_default();
+                out.push('\n');
+            }
+            Self::Hosts => {
+                for r in rewrites {
+                    if r.answer.parse::<IpAddr>().is_ok() && !r.domain.starts_with("*.") {
+                        out.push_str(&format!("{} {}\n", r.answer, r.domain));
+                    } else {
+                        out.push_str(&format!(
+                            "# not representable: {} -> {}\n",
+                            r.domain, r.answer
+                        ));
+                    }
+                }
+            }
+            Self::Dnsmasq => {
+                let mut covered = vec![false; rewrites.len()];
+                for (i, wildcard) in rewrites.iter().enumerate() {
+                    let Some(base) = wildcard.domain.strip_prefix("*.") else {
+                        continue;
+                    };
+                    // address=/d/ip is d plus all its subdomains: only exact when both rewrites exist
+                    if let Some(j) = rewrites
+                        .iter()
+                        .position(|r| r.domain == base && r.answer == wildcard.answer)
+                        && wildcard.answer.parse::<IpAddr>().is_ok()
+                    {
+                        out.push_str(&format!("address=/{}/{}\n", base, wildcard.answer));
+                        covered[i] = true;
+                        covered[j] = true;
+                    }
+                }
+                for (r, _) in rewrites.iter().zip(covered).filter(|(_, c)| !c) {
+                    if r.domain.starts_with("*.") {
+                        out.push_str(&format!(
+                            "# not representable: {} -> {}\n",
+                            r.domain, r.answer
+                        ));
+                    } else if r.answer.parse::<IpAddr>().is_ok() {
+                        out.push_str(&format!("host-record={},{}\n", r.domain, r.answer));
+                    } else {
+                        out.push_str(&format!("cname={},{}\n", r.domain, r.answer));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn content_lines(text: &str, inline_comments: bool) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(move |(i, line)| {
+            let line = if inline_comments {
+                line.split('#').next().unwrap_or_default()
+            } else {
+                line
+            };
+            (i + 1, line.trim())
+        })
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn is_loopback_name(name: &str) -> bool {
+    matches!(
+        name,
+        "localhost" | "localhost.localdomain" | "broadcasthost"
+    ) || name.starts_with("ip6-")
+}
+
+fn push_rewrite(rewrites: &mut Vec<DnsRewrite>, domain: &str, answer: &str) {
+    let rewrite = DnsRewrite {
+        domain: domain.trim().trim_end_matches('.').to_ascii_lowercase(),
+        answer: answer.trim().to_string(),
+        extra: Default::default(),
+    };
+    if !rewrites.contains(&rewrite) {
+        rewrites.push(rewrite);
+    }
+}
+
+/// Changes needed to bring an instance's rewrites in line with an import.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct RewriteDiff {
+    pub add: Vec<DnsRewrite>,
+    /// Domains whose single answer changes, applied with `/control/rewrite/update`.
+    pub update: Vec<(DnsRewrite, DnsRewrite)>,
+    pub remove: Vec<DnsRewrite>,
+    pub unchanged: usize,
+}
+
+impl RewriteDiff {
+    /// Compares `current` with `desired`. With `overwrite`, rewrites missing from
+    /// `desired` are removed; otherwise they are kept (additive merge).
+    pub fn compute(current: &[DnsRewrite], desired: &[DnsRewrite], overwrite: bool) -> Self {
+        let mut diff = Self {
+            unchanged: desired.iter().filter(|r| current.contains(r)).count(),
+            ..Default::default()
+        };
+        let mut add: Vec<DnsRewrite> = desired
+            .iter()
+            .filter(|r| !current.contains(r))
+            .cloned()
+            .collect();
+        if !overwrite {
+            diff.add = add;
+            return diff;
+        }
+
+        let removed: Vec<&DnsRewrite> = current.iter().filter(|r| !desired.contains(r)).collect();
+        for old in &removed {
+            // A domain losing exactly one answer and gaining exactly one is edited in place
+            let removed_once = removed.iter().filter(|r| r.domain == old.domain).count() == 1;
+            let added_once = add.iter().filter(|r| r.domain == old.domain).count() == 1;
+            match add.iter().position(|r| r.domain == old.domain) {
+                Some(pos) if removed_once && added_once => {
+                    // Fields the import does not set keep the server's values
+                    let mut new = add.remove(pos);
+                    let mut extra = old.extra.clone();
+                    extra.extend(new.extra);
+                    new.extra = extra;
+                    diff.update.push(((*old).clone(), new));
+                }
+                _ => diff.remove.push((*old).clone()),
+            }
+        }
+        diff.add = add;
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.update.is_empty() && self.remove.is_empty()
+    }
+}
+
+impl std::fmt::Display for RewriteDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} to add, {} to update, {} to remove, {} unchanged",
+            self.add.len(),
+            self.update.len(),
+            self.remove.len(),
+            self.unchanged
+        )?;
+        for r in &self.add {
+            writeln!(f, "+ {} -> {}", r.domain, r.answer)?;
+        }
+        for (old, new) in &self.update {
+            writeln!(f, "~ {}: {} -> {}", old.domain, old.answer, new.answer)?;
+        }
+        for r in &self.remove {
+            writeln!(f, "- {} -> {}", r.domain, r.answer)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const MS_PER_MINUTE: u64 = 60 * 1000;
+const MS_PER_DAY: u64 = 24 * 60 * MS_PER_MINUTE;
+
+pub const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Time window within a day, in milliseconds since midnight as AdGuard Home expects.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DayRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Weekly schedule during which service blocking is paused.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct WeeklySchedule {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub time_zone: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sun: Option<DayRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mon: Option<DayRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tue: Option<DayRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wed: Option<DayRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thu: Option<DayRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fri: Option<DayRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sat: Option<DayRange>,
+}
+
+impl WeeklySchedule {
+    /// Parses tool input, accepting either milliseconds or "HH:MM" for each
+    /// `start`/`end`, and validates the result.
+    pub fn from_params(value: &serde_json::Value) -> Result<Self, String> {
+        let Some(object) = value.as_object() else {
+            return Err("schedule must be an object".to_string());
+        };
+
+        let mut normalized = serde_json::Map::new();
+        for (key, day) in object {
+            if key == "time_zone" {
+                normalized.insert(key.clone(), day.clone());
+                continue;
+            }
+            if !WEEKDAYS.contains(&key.as_str()) {
+                return Err(format!("Unknown schedule day: {}", key));
+            }
+            if day.is_null() {
+                continue;
+            }
+            let start = parse_time(&day["start"]).map_err(|e| format!("{}.start: {}", key, e))?;
+            let end = parse_time(&day["end"]).map_err(|e| format!("{}.end: {}", key, e))?;
+            normalized.insert(
+                key.clone(),
+                serde_json::json!({ "start": start, "end": end }),
+            );
+        }
+
+        let schedule: Self = serde_json::from_value(serde_json::Value::Object(normalized))
+            .map_err(|e| e.to_string())?;
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    pub fn days(&self) -> [(&'static str, Option<DayRange>); 7] {
+        [
+            ("sun", self.sun),
+            ("mon", self.mon),
+            ("tue", self.tue),
+            ("wed", self.wed),
+            ("thu", self.thu),
+            ("fri", self.fri),
+            ("sat", self.sat),
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (day, range) in self.days() {
+            let Some(range) = range else { continue };
+            if range.end > MS_PER_DAY {
+                return Err(format!("{}: end is past midnight", day));
+            }
+            if range.start >= range.end {
+                return Err(format!("{}: start must be before end", day));
+            }
+            if range.start % MS_PER_MINUTE != 0 || range.end % MS_PER_MINUTE != 0 {
+                return Err(format!("{}: times must be whole minutes", day));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WeeklySchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time_zone = if self.time_zone.is_empty() {
+            "Local"
+        } else {
+            &self.time_zone
+        };
+        writeln!(f, "Time zone: {}", time_zone)?;
+
+        let paused: Vec<_> = self
+            .days()
+            .into_iter()
+            .filter_map(|(day, range)| range.map(|r| (day, r)))
+            .collect();
+        if paused.is_empty() {
+            return writeln!(f, "No pauses: blocked services are blocked all week.");
+        }
+        writeln!(f, "Service blocking is paused:")?;
+        for (day, range) in paused {
+            writeln!(
+                f,
+                "- {}: {} - {}",
+                day_name(day),
+                format_time(range.start),
+                format_time(range.end)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn day_name(day: &str) -> &'static str {
+    match day {
+        "sun" => "Sunday",
+        "mon" => "Monday",
+        "tue" => "Tuesday",
+        "wed" => "Wednesday",
+        "thu" => "Thursday",
+        "fri" => "Friday",
+        _ => "Saturday",
+    }
+}
+
+fn format_time(ms: u64) -> String {
+    let minutes = ms / MS_PER_MINUTE;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn parse_time(value: &serde_json::Value) -> Result<u64, String> {
+    if let Some(ms) = value.as_u64() {
+        return Ok(ms);
+    }
+    let Some(text) = value.as_str() else {
+        return Err("expected milliseconds or \"HH:MM\"".to_string());
+    };
+    let (hours, minutes) = text
+        .split_once(':')
+        .and_then(|(h, m)| Some((h.parse::<u64>().ok()?, m.parse::<u64>().ok()?)))
+        .ok_or_else(|| format!("invalid time '{}', expected \"HH:MM\"", text))?;
+    if minutes >= 60 || hours > 24 || (hours == 24 && minutes > 0) {
+        return Err(format!("invalid time '{}'", text));
+    }
+    Ok((hours * 60 + minutes) * MS_PER_MINUTE)
+}
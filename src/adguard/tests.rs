@@ -157,6 +157,7 @@ async fn test_configure_tls() {
         valid_cert: true,
         valid_key: true,
         valid_pair: true,
+        ..Default::default()
     };
     client.configure_tls(tls_config).await.unwrap();
 }
@@ -216,6 +217,7 @@ async fn test_validate_tls() {
         valid_cert: true,
         valid_key: true,
         valid_pair: true,
+        ..Default::default()
     };
     let result = client.validate_tls(tls_config).await.unwrap();
     assert!(result.valid_cert);
@@ -451,6 +453,7 @@ async fn test_list_rewrites() {
         .respond_with(ResponseTemplate::new(200).set_body_json(vec![DnsRewrite {
             domain: "example.com".to_string(),
             answer: "1.2.3.4".to_string(),
+            extra: Default::default(),
         }]))
         .mount(&server)
         .await;
@@ -490,6 +493,7 @@ async fn test_add_rewrite() {
     let rewrite = DnsRewrite {
         domain: "example.com".to_string(),
         answer: "1.2.3.4".to_string(),
+        extra: Default::default(),
     };
     client.add_rewrite(rewrite).await.unwrap();
 }
@@ -524,6 +528,7 @@ async fn test_delete_rewrite() {
     let rewrite = DnsRewrite {
         domain: "example.com".to_string(),
         answer: "1.2.3.4".to_string(),
+        extra: Default::default(),
     };
     client.delete_rewrite(rewrite).await.unwrap();
 }
@@ -683,7 +688,7 @@ async fn test_get_query_log_with_params() {
     Mock::given(method("GET"))
         .and(path("/control/querylog"))
         .and(wiremock::matchers::query_param("search", "google"))
-        .and(wiremock::matchers::query_param("filter", "all"))
+        .and(wiremock::matchers::query_param("response_status", "all"))
         .and(wiremock::matchers::query_param("limit", "10"))
         .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
             "data": []
@@ -698,6 +703,183 @@ async fn test_get_query_log_with_params() {
     assert_eq!(log.data.len(), 0);
 }
 
+fn query_log_entry(client: &str, name: &str, time: &str) -> serde_json::Value {
+    serde_json::json!({
+        "client": client,
+        "elapsed_ms": "0.1",
+        "reason": "NotFilteredNotFound",
+        "status": "NOERROR",
+        "time": time,
+        "question": { "name": name, "type": "A" }
+    })
+}
+
+#[tokio::test]
+async fn test_query_log_encodes_params_and_filters_client() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/querylog"))
+        .and(wiremock::matchers::query_param("search", "a&b c"))
+        .and(wiremock::matchers::query_param(
+            "response_status",
+            "blocked_parental",
+        ))
+        .and(wiremock::matchers::query_param(
+            "older_than",
+            "2024-01-01T00:00:00+01:00",
+        ))
+        .and(wiremock::matchers::query_param("offset", "20"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [
+                query_log_entry("10.0.0.2", "a&b c.example", "2023-12-31T22:00:00Z"),
+                query_log_entry("10.0.0.3", "a&b c.example", "2023-12-31T21:00:00Z")
+            ],
+            "oldest": "2023-12-31T21:00:00Z"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let log = client
+        .query_log(&QueryLogParams {
+            search: Some("a&b c".to_string()),
+            response_status: Some("blocked_parental".to_string()),
+            older_than: Some("2024-01-01T00:00:00+01:00".to_string()),
+            offset: Some(20),
+            client: Some("10.0.0.2".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(log.data.len(), 1);
+    assert_eq!(log.data[0].client, "10.0.0.2");
+    assert_eq!(log.oldest, "2023-12-31T21:00:00Z");
+
+    let err = client
+        .query_log(&QueryLogParams {
+            response_status: Some("allowed".to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert!(err.is_err());
+}
+
+#[tokio::test]
+async fn test_query_log_entry_details() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/querylog"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{
+                "client": "10.0.0.5",
+                "client_proto": "doh",
+                "client_info": { "name": "laptop", "whois": { "country": "US" }, "disallowed": false, "disallowed_rule": "" },
+                "elapsed_ms": "1.2",
+                "reason": "Rewrite",
+                "status": "NOERROR",
+                "time": "2024-01-01T00:00:00Z",
+                "question": { "name": "nas.lan", "type": "A", "class": "IN" },
+                "answer": [{ "type": "A", "value": "192.168.1.10", "ttl": 10 }],
+                "original_answer": null,
+                "upstream": "https://dns.example/dns-query",
+                "cached": true,
+                "answer_dnssec": true,
+                "rules": [{ "filter_list_id": 0, "text": "||nas.lan^$dnsrewrite=192.168.1.10" }],
+                "service_name": ""
+            }],
+            "oldest": "2024-01-01T00:00:00Z"
+        })))
+        .mount(&server)
+        .await;
+
+    let log = client
+        .query_log(&QueryLogParams {
+            client: Some("Laptop".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(log.data.len(), 1);
+    let entry = &log.data[0];
+    assert_eq!(entry.client_proto, "doh");
+    assert_eq!(entry.client_info.as_ref().unwrap().whois["country"], "US");
+    assert_eq!(entry.answer[0].value, "192.168.1.10");
+    assert!(entry.original_answer.is_empty());
+    assert!(entry.cached);
+    assert!(entry.answer_dnssec);
+    assert_eq!(entry.rules[0].text, "||nas.lan^$dnsrewrite=192.168.1.10");
+}
+
+#[tokio::test]
+async fn test_query_log_pages_until_time_bound() {
+    use futures::TryStreamExt;
+
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/querylog"))
+        .and(wiremock::matchers::query_param(
+            "older_than",
+            "2024-01-01T10:00:00Z",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [
+                query_log_entry("10.0.0.1", "c.example", "2024-01-01T09:00:00Z"),
+                query_log_entry("10.0.0.1", "d.example", "2024-01-01T07:00:00Z")
+            ],
+            "oldest": "2024-01-01T07:00:00Z"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/control/querylog"))
+        .and(wiremock::matchers::query_param_is_missing("older_than"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [
+                query_log_entry("10.0.0.1", "a.example", "2024-01-01T11:00:00Z"),
+                query_log_entry("10.0.0.1", "b.example", "2024-01-01T10:00:00Z")
+            ],
+            "oldest": "2024-01-01T10:00:00Z"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let until = "2024-01-01T08:00:00Z".parse().unwrap();
+    let pages: Vec<_> = client
+        .query_log_pages(
+            QueryLogParams {
+                limit: Some(2),
+                ..Default::default()
+            },
+            Some(until),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+    let names: Vec<_> = pages
+        .iter()
+        .flatten()
+        .map(|e| e.question.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["a.example", "b.example", "c.example"]);
+}
+
 #[tokio::test]
 async fn test_get_stats_with_period() {
     let server = MockServer::start().await;
@@ -769,6 +951,7 @@ async fn test_set_parental_settings_disabled() {
     let settings = ParentalControlConfig {
         enabled: false,
         sensitivity: None,
+        extra: Default::default(),
     };
     client.set_parental_settings(settings).await.unwrap();
 }
@@ -1333,6 +1516,7 @@ async fn test_set_safe_search_settings() {
         pixabay: true,
         yandex: true,
         youtube: true,
+        extra: Default::default(),
     };
     client.set_safe_search_settings(settings).await.unwrap();
 }
@@ -1402,6 +1586,7 @@ async fn test_set_parental_settings() {
     let settings = ParentalControlConfig {
         enabled: true,
         sensitivity: None,
+        extra: Default::default(),
     };
     client.set_parental_settings(settings).await.unwrap();
 }
@@ -1477,6 +1662,7 @@ async fn test_set_query_log_config() {
         anonymize_client_ip: false,
         allowed_clients: vec![],
         disallowed_clients: vec![],
+        extra: Default::default(),
     };
     client.set_query_log_config(config).await.unwrap();
 }
@@ -1810,6 +1996,7 @@ async fn test_add_client() {
         parental_enabled: false,
         safebrowsing_enabled: true,
         safesearch_enabled: false,
+        ..Default::default()
     };
     client.add_client(device).await.unwrap();
 }
@@ -1849,6 +2036,7 @@ async fn test_update_client() {
         parental_enabled: false,
         safebrowsing_enabled: true,
         safesearch_enabled: false,
+        ..Default::default()
     };
     client
         .update_client("Old Client".to_string(), device)
@@ -2002,6 +2190,59 @@ async fn test_remove_static_lease() {
     client.remove_static_lease(lease).await.unwrap();
 }
 
+#[tokio::test]
+async fn test_update_static_lease_fallback_restores_on_failure() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+    let original =
+        serde_json::json!({ "mac": "00:11:22:33:44:55", "ip": "192.168.1.10", "hostname": "nas" });
+
+    Mock::given(method("POST"))
+        .and(path("/control/dhcp/update_static_lease"))
+        .respond_with(ResponseTemplate::new(405))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/control/dhcp/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "enabled": true, "interface_name": "eth0", "leases": [], "static_leases": [original]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/control/dhcp/remove_static_lease"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/control/dhcp/add_static_lease"))
+        .and(wiremock::matchers::body_json(serde_json::json!({
+            "mac": "00:11:22:33:44:55", "ip": "10.0.0.10", "hostname": "nas"
+        })))
+        .respond_with(ResponseTemplate::new(400))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/control/dhcp/add_static_lease"))
+        .and(wiremock::matchers::body_json(original))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let lease = StaticLease {
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "10.0.0.10".to_string(),
+        hostname: "nas".to_string(),
+    };
+    assert!(client.update_static_lease(lease).await.is_err());
+}
+
 #[tokio::test]
 async fn test_get_dhcp_config() {
     let server = MockServer::start().await;
@@ -2087,10 +2328,12 @@ async fn test_set_dhcp_config() {
             range_start: Some("192.168.1.10".to_string()),
             range_end: Some("192.168.1.100".to_string()),
             lease_duration: Some(86400),
+            extra: Default::default(),
         }),
         v6: None,
         leases: vec![],
         static_leases: vec![],
+        extra: Default::default(),
     };
     client.set_dhcp_config(dhcp_config).await.unwrap();
 }
@@ -2162,6 +2405,7 @@ async fn test_set_profile_info() {
         name: "admin".to_string(),
         language: "en".to_string(),
         theme: "dark".to_string(),
+        extra: Default::default(),
     };
     client.set_profile_info(profile).await.unwrap();
 }
@@ -2255,6 +2499,7 @@ async fn test_set_dns_config() {
         upstream_mode: "".to_string(),
         use_private_ptr_resolvers: true,
         local_ptr_upstreams: vec![],
+        ..Default::default()
     };
     client.set_dns_config(dns_config).await.unwrap();
 }
@@ -2356,6 +2601,7 @@ async fn test_set_access_list() {
         allowed_clients: vec!["192.168.1.10".to_string()],
         disallowed_clients: vec![],
         blocked_hosts: vec!["malicious.com".to_string()],
+        extra: Default::default(),
     };
     client.set_access_list(list).await.unwrap();
 }
@@ -2481,3 +2727,841 @@ async fn test_get_tls_status() {
     assert!(status.enabled);
     assert_eq!(status.server_name, "example.com");
 }
+
+#[tokio::test]
+async fn test_sub_path_and_custom_headers() {
+    let server = MockServer::start().await;
+    let mut headers = std::collections::HashMap::new();
+    headers.insert("CF-Access-Client-Id".to_string(), "client-id".to_string());
+    let client = AdGuardClient::try_new(crate::config::InstanceConfig {
+        url: format!("{}/adguard/", server.uri()),
+        headers,
+        ..Default::default()
+    })
+    .unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/adguard/control/status"))
+        .and(wiremock::matchers::header(
+            "CF-Access-Client-Id",
+            "client-id",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "version": "v0.107.0",
+            "language": "en",
+            "protection_enabled": true
+        })))
+        .mount(&server)
+        .await;
+
+    let status = client.get_status().await.unwrap();
+    assert_eq!(status.version, "v0.107.0");
+}
+
+#[tokio::test]
+async fn test_try_new_invalid_settings() {
+    let missing_ca = AdGuardClient::try_new(crate::config::InstanceConfig {
+        url: "http://localhost".to_string(),
+        ca_cert_path: Some("/nonexistent/ca.pem".to_string()),
+        ..Default::default()
+    });
+    assert!(missing_ca.is_err());
+
+    let mut headers = std::collections::HashMap::new();
+    headers.insert("Bad Header".to_string(), "value".to_string());
+    let bad_header = AdGuardClient::try_new(crate::config::InstanceConfig {
+        url: "http://localhost".to_string(),
+        headers,
+        ..Default::default()
+    });
+    assert!(bad_header.is_err());
+
+    let proxied = AdGuardClient::try_new(crate::config::InstanceConfig {
+        url: "http://localhost".to_string(),
+        proxy: Some("socks5://127.0.0.1:1080".to_string()),
+        ..Default::default()
+    });
+    assert!(proxied.is_ok());
+}
+
+#[test]
+fn test_capabilities_for_version() {
+    let legacy = Capabilities::for_version("v0.107.20");
+    assert!(!legacy.querylog_config_v1);
+    assert!(!legacy.blocked_services_schedule);
+    assert!(!legacy.clients_search);
+
+    let modern = Capabilities::for_version("v0.107.57");
+    assert!(modern.querylog_config_v1);
+    assert!(modern.stats_config_v1);
+    assert!(modern.blocked_services_schedule);
+    assert!(modern.clients_search);
+    assert!(modern.rewrite_update);
+
+    let unknown = Capabilities::for_version("edge");
+    assert_eq!(unknown.version.as_deref(), Some("edge"));
+    assert_eq!(
+        unknown.querylog_config_v1,
+        Capabilities::assumed().querylog_config_v1
+    );
+
+    let err = legacy
+        .require(
+            legacy.blocked_services_schedule,
+            "Blocked services schedule",
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("v0.107.20"));
+}
+
+#[tokio::test]
+async fn test_capability_routing_legacy_query_log_config() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "version": "v0.107.20",
+            "language": "en",
+            "protection_enabled": true
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/control/querylog_info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "enabled": true,
+            "interval": 7,
+            "anonymize_client_ip": false
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/control/querylog_config"))
+        .and(wiremock::matchers::body_partial_json(
+            serde_json::json!({ "interval": 7 }),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = client.get_query_log_config().await.unwrap();
+    assert_eq!(config.interval, 7 * 24 * 60 * 60 * 1000);
+    client.set_query_log_config(config).await.unwrap();
+
+    // Unsupported actions fail cleanly instead of with a 404
+    let err = client.get_blocked_services_config().await.unwrap_err();
+    assert!(matches!(err, crate::error::Error::Unsupported(_)));
+}
+
+#[tokio::test]
+async fn test_client_cache_probes_each_instance_once() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/control/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "version": "v0.107.50",
+            "language": "en",
+            "protection_enabled": true
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let cache = ClientCache::default();
+    let mut instance = crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    };
+    for _ in 0..2 {
+        let client = cache.clone().get(&instance).unwrap();
+        let caps = client.capabilities().await;
+        assert_eq!(caps.version.as_deref(), Some("v0.107.50"));
+    }
+
+    // Other credentials for the same URL get a client of their own
+    instance.api_key = Some("other".to_string());
+    cache.get(&instance).unwrap().capabilities().await;
+}
+
+#[tokio::test]
+async fn test_stats_config_routing() {
+    for (version, legacy) in [("v0.107.20", true), ("v0.107.50", false)] {
+        let server = MockServer::start().await;
+        let client = AdGuardClient::new(crate::config::InstanceConfig {
+            url: server.uri(),
+            ..Default::default()
+        });
+
+        Mock::given(method("GET"))
+            .and(path("/control/status"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "version": version,
+                "language": "en",
+                "protection_enabled": true
+            })))
+            .mount(&server)
+            .await;
+
+        if legacy {
+            Mock::given(method("GET"))
+                .and(path("/control/stats_info"))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(serde_json::json!({ "interval": 1 })),
+                )
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/control/stats_config"))
+                .and(wiremock::matchers::body_json(
+                    serde_json::json!({ "interval": 1 }),
+                ))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        } else {
+            Mock::given(method("GET"))
+                .and(path("/control/stats/config"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "enabled": true,
+                    "interval": 86400000,
+                    "ignored": ["example.org"]
+                })))
+                .mount(&server)
+                .await;
+            Mock::given(method("PUT"))
+                .and(path("/control/stats/config/update"))
+                .and(wiremock::matchers::body_json(serde_json::json!({
+                    "enabled": true,
+                    "interval": 86400000,
+                    "ignored": ["example.org"]
+                })))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let config = client.get_stats_config().await.unwrap();
+        assert_eq!(config.interval, 24 * 60 * 60 * 1000);
+        assert!(config.enabled);
+        assert_eq!(config.ignored.is_empty(), legacy);
+        client.set_stats_config(config.clone()).await.unwrap();
+
+        if legacy {
+            // Settings the legacy endpoint cannot store are refused, not dropped
+            let rejected = [
+                StatsConfig {
+                    enabled: false,
+                    ..config.clone()
+                },
+                StatsConfig {
+                    ignored: vec!["example.org".to_string()],
+                    ..config.clone()
+                },
+                StatsConfig {
+                    interval: 2 * 24 * 60 * 60 * 1000,
+                    ..config.clone()
+                },
+            ];
+            for config in rejected {
+                let err = client.set_stats_config(config).await.unwrap_err();
+                assert!(matches!(err, crate::error::Error::Unsupported(_)));
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_capability_routing_blocked_services_schedule() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "version": "v0.107.57",
+            "language": "en",
+            "protection_enabled": true
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/control/blocked_services/get"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ids": ["youtube"],
+            "schedule": { "time_zone": "UTC" }
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path("/control/blocked_services/update"))
+        .and(wiremock::matchers::body_json(serde_json::json!({
+            "ids": ["youtube", "tiktok"],
+            "schedule": { "time_zone": "UTC" }
+        })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let ids = client.list_blocked_services().await.unwrap();
+    assert_eq!(ids, vec!["youtube".to_string()]);
+    client
+        .set_blocked_services(vec!["youtube".to_string(), "tiktok".to_string()])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_get_client_info_falls_back_to_search() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "version": "v0.107.57",
+            "language": "en",
+            "protection_enabled": true
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/control/clients"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "clients": [{
+                "name": "LAN",
+                "ids": ["192.168.1.0/24"],
+                "use_global_settings": true,
+                "filtering_enabled": true,
+                "parental_enabled": false,
+                "safebrowsing_enabled": false,
+                "safesearch_enabled": false
+            }]
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/control/clients/search"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                "192.168.1.5": {
+                    "name": "LAN",
+                    "ids": ["192.168.1.0/24"],
+                    "use_global_settings": true,
+                    "filtering_enabled": true,
+                    "parental_enabled": false,
+                    "safebrowsing_enabled": false,
+                    "safesearch_enabled": false
+                }
+            }])),
+        )
+        .mount(&server)
+        .await;
+
+    let found = client.get_client_info("192.168.1.5").await.unwrap();
+    assert_eq!(found.name, "LAN");
+}
+
+#[tokio::test]
+async fn test_dns_config_round_trips_unknown_fields() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/dns_info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "upstream_dns": ["1.1.1.1"],
+            "cache_size": 4096,
+            "blocking_mode": "nxdomain",
+            "ratelimit": 30,
+            "dnssec_enabled": true,
+            "protection_disabled_until": null,
+            "default_local_ptr_upstreams": ["192.168.1.1"]
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/control/dns_config"))
+        .and(wiremock::matchers::body_partial_json(serde_json::json!({
+            "upstream_dns": ["1.1.1.1"],
+            "cache_size": 8192,
+            "blocking_mode": "nxdomain",
+            "ratelimit": 30,
+            "dnssec_enabled": true,
+            "default_local_ptr_upstreams": ["192.168.1.1"]
+        })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut config = client.get_dns_info().await.unwrap();
+    assert_eq!(config.blocking_mode.as_deref(), Some("nxdomain"));
+    assert_eq!(
+        config.extra.get("default_local_ptr_upstreams"),
+        Some(&serde_json::json!(["192.168.1.1"]))
+    );
+    config.cache_size = 8192;
+    client.set_dns_config(config).await.unwrap();
+}
+
+#[test]
+fn test_filtering_and_rewrite_models_keep_unknown_fields() {
+    let filtering: FilteringConfig = serde_json::from_value(serde_json::json!({
+        "enabled": true,
+        "interval": 24,
+        "filters": [{
+            "url": "https://a", "name": "A", "id": 1, "enabled": true,
+            "last_updated": null, "rules_count": 10, "checksum": 42
+        }],
+        "user_rules": [],
+        "rules_limit": 1000
+    }))
+    .unwrap();
+    let value = serde_json::to_value(&filtering).unwrap();
+    assert_eq!(value["rules_limit"], 1000);
+    assert_eq!(value["filters"][0]["checksum"], 42);
+
+    let parental: ParentalControlConfig =
+        serde_json::from_value(serde_json::json!({ "enabled": true, "cache_ttl": 60 })).unwrap();
+    assert_eq!(serde_json::to_value(&parental).unwrap()["cache_ttl"], 60);
+
+    let rewrite: DnsRewrite = serde_json::from_value(serde_json::json!({
+        "domain": "nas.lan", "answer": "192.168.1.10", "enabled": false
+    }))
+    .unwrap();
+    assert_eq!(serde_json::to_value(&rewrite).unwrap()["enabled"], false);
+    // Identity stays domain and answer
+    assert_eq!(
+        rewrite,
+        DnsRewrite {
+            domain: "nas.lan".to_string(),
+            answer: "192.168.1.10".to_string(),
+            extra: Default::default(),
+        }
+    );
+}
+
+#[test]
+fn test_weekly_schedule_parsing_and_display() {
+    let schedule = WeeklySchedule::from_params(&serde_json::json!({
+        "time_zone": "America/New_York",
+        "mon": { "start": "07:30", "end": "15:00" },
+        "sat": { "start": 0, "end": 86400000 }
+    }))
+    .unwrap();
+    assert_eq!(
+        schedule.mon,
+        Some(DayRange {
+            start: 27_000_000,
+            end: 54_000_000
+        })
+    );
+    assert_eq!(
+        schedule.to_string(),
+        "Time zone: America/New_York\nService blocking is paused:\n- Monday: 07:30 - 15:00\n- Saturday: 00:00 - 24:00\n"
+    );
+
+    for invalid in [
+        serde_json::json!({ "mon": { "start": "15:00", "end": "07:00" } }),
+        serde_json::json!({ "mon": { "start": "00:00", "end": "24:30" } }),
+        serde_json::json!({ "funday": { "start": "00:00", "end": "01:00" } }),
+        serde_json::json!({ "tue": { "start": "noon", "end": "13:00" } }),
+        serde_json::json!({ "wed": { "start": 1, "end": 60000 } }),
+    ] {
+        assert!(
+            WeeklySchedule::from_params(&invalid).is_err(),
+            "{}",
+            invalid
+        );
+    }
+
+    assert_eq!(
+        WeeklySchedule::default().to_string(),
+        "Time zone: Local\nNo pauses: blocked services are blocked all week.\n"
+    );
+}
+
+#[tokio::test]
+async fn test_discover_clients_merges_sources() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/clients"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "clients": [{
+                "name": "NAS", "ids": ["AA:BB:CC:00:00:01"], "use_global_settings": true,
+                "filtering_enabled": true, "parental_enabled": false,
+                "safebrowsing_enabled": true, "safesearch_enabled": false
+            }],
+            "auto_clients": [
+                { "ip": "192.168.1.10", "name": "nas.lan", "source": "rDNS", "whois_info": {} },
+                { "ip": "192.168.1.20", "name": "phone.lan", "source": "ARP", "whois_info": {} }
+            ]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/control/dhcp/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "enabled": true,
+            "interface_name": "eth0",
+            "leases": [{ "mac": "aa:bb:cc:00:00:02", "ip": "192.168.1.20", "hostname": "phone", "expires": "" }],
+            "static_leases": [{ "mac": "aa:bb:cc:00:00:01", "ip": "192.168.1.10", "hostname": "nas" }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/control/querylog"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [
+                query_log_entry("192.168.1.20", "a.example", "2024-01-01T10:00:00Z"),
+                query_log_entry("192.168.1.20", "b.example", "2024-01-01T09:00:00Z"),
+                query_log_entry("192.168.1.30", "c.example", "2024-01-01T08:00:00Z")
+            ],
+            "oldest": ""
+        })))
+        .mount(&server)
+        .await;
+
+    let devices = client.discover_clients(100).await.unwrap();
+    assert_eq!(devices.len(), 3);
+
+    let phone = &devices[0];
+    assert_eq!(phone.ips, vec!["192.168.1.20"]);
+    assert_eq!(phone.macs, vec!["aa:bb:cc:00:00:02"]);
+    assert_eq!(phone.names, vec!["phone", "phone.lan"]);
+    assert_eq!(phone.sources, vec!["DHCP lease", "ARP", "query log"]);
+    assert_eq!(phone.queries, 2);
+    assert_eq!(phone.last_seen.as_deref(), Some("2024-01-01T10:00:00Z"));
+    assert!(phone.persistent_client.is_none());
+
+    let nas = devices.iter().find(|d| d.matches("nas.lan")).unwrap();
+    assert_eq!(nas.persistent_client.as_deref(), Some("NAS"));
+
+    let unknown = devices.iter().find(|d| d.matches("192.168.1.30")).unwrap();
+    assert_eq!(unknown.sources, vec!["query log"]);
+}
+
+#[test]
+fn test_oui_lookup() {
+    let pi = oui::lookup("B8-27-EB-12-34-56").unwrap();
+    assert_eq!(pi.vendor, Some("Raspberry Pi Foundation"));
+    assert!(!pi.locally_administered);
+
+    let esp = oui::lookup("240ac4123456").unwrap();
+    assert_eq!(esp.label(), "Espressif Inc. (IoT)");
+
+    let private = oui::lookup("da:a1:19:00:00:01").unwrap();
+    assert!(private.locally_administered);
+    assert!(private.randomized);
+
+    let qemu = oui::lookup("52:54:00:12:34:56").unwrap();
+    assert!(qemu.locally_administered);
+    assert!(!qemu.randomized);
+    assert_eq!(qemu.vendor, Some("QEMU/KVM virtual NIC"));
+
+    assert!(oui::lookup("192.168.1.1").is_none());
+    assert_eq!(oui::annotate("laptop"), "laptop");
+}
+
+#[tokio::test]
+async fn test_preview_filter() {
+    let server = MockServer::start().await;
+    let list = "! Title: Test List\n! Homepage: https://example.org\n||ads.example.com^\n@@||good.example.com^\n0.0.0.0 tracker.example.net\nplain.example.org\n/banner\\d+/\nexample.com##.ad\n";
+    Mock::given(method("GET"))
+        .and(path("/list.txt"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(list))
+        .mount(&server)
+        .await;
+
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+    let preview = client
+        .preview_filter(&format!("{}/list.txt", server.uri()))
+        .await
+        .unwrap();
+
+    assert_eq!(preview.title.as_deref(), Some("Test List"));
+    assert_eq!(preview.comments, 2);
+    assert_eq!(preview.rules, 6);
+    assert_eq!(preview.adblock, 1);
+    assert_eq!(preview.exceptions, 1);
+    assert_eq!(preview.hosts, 1);
+    assert_eq!(preview.domains, 1);
+    assert_eq!(preview.regex, 1);
+    assert_eq!(preview.cosmetic, 1);
+    assert_eq!(preview.samples[0], "||ads.example.com^");
+}
+
+#[test]
+fn test_rewrite_formats_round_trip() {
+    let hosts = "127.0.0.1 localhost\n192.168.1.10 NAS.lan nas.\n# printer\n192.168.1.20 printer.lan # office\n";
+    let parsed = RewriteFormat::Hosts.parse(hosts).unwrap();
+    assert_eq!(
+        parsed
+            .iter()
+            .map(|r| (r.domain.as_str(), r.answer.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("nas.lan", "192.168.1.10"),
+            ("nas", "192.168.1.10"),
+            ("printer.lan", "192.168.1.20"),
+        ]
+    );
+    assert!(RewriteFormat::Hosts.parse("nas.lan 192.168.1.10").is_err());
+
+    let dnsmasq = "address=/home.lan/192.168.1.5\nhost-record=nas.lan,192.168.1.10\ncname=www.lan,nas.lan\nserver=1.1.1.1\n";
+    let parsed = RewriteFormat::Dnsmasq.parse(dnsmasq).unwrap();
+    assert_eq!(parsed.len(), 4);
+    assert!(parsed.contains(&DnsRewrite {
+        domain: "*.home.lan".to_string(),
+        answer: "192.168.1.5".to_string(),
+        extra: Default::default(),
+    }));
+    assert_eq!(
+        RewriteFormat::Dnsmasq.format(&parsed),
+        dnsmasq.replace("server=1.1.1.1\n", "")
+    );
+
+    // dnsmasq blocking entries are skipped with a warning, not imported
+    let (blocking, warnings) = RewriteFormat::Dnsmasq
+        .parse_with_warnings(
+            "# ads\naddress=/ads.lan/#\naddress=/track.lan/\naddress=/home.lan/192.168.1.5\n",
+        )
+        .unwrap();
+    assert_eq!(blocking.len(), 2);
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].starts_with("line 2: blocking entry"));
+
+    let exported = RewriteFormat::Hosts.format(&parsed);
+    assert!(exported.contains("192.168.1.5 home.lan\n"));
+    assert!(exported.contains("# not representable: www.lan -> nas.lan\n"));
+
+    let json = RewriteFormat::Json.format(&parsed);
+    assert_eq!(RewriteFormat::Json.parse(&json).unwrap(), parsed);
+}
+
+#[test]
+fn test_rewrite_diff() {
+    let rewrite = |domain: &str, answer: &str| DnsRewrite {
+        domain: domain.to_string(),
+        answer: answer.to_string(),
+        extra: Default::default(),
+    };
+    let current = vec![
+        rewrite("nas.lan", "192.168.1.10"),
+        rewrite("old.lan", "192.168.1.99"),
+        rewrite("printer.lan", "192.168.1.20"),
+    ];
+    let desired = vec![
+        rewrite("nas.lan", "192.168.1.10"),
+        rewrite("printer.lan", "192.168.1.21"),
+        rewrite("tv.lan", "192.168.1.30"),
+    ];
+
+    let merge = RewriteDiff::compute(&current, &desired, false);
+    assert_eq!(merge.add.len(), 2);
+    assert!(merge.update.is_empty() && merge.remove.is_empty());
+
+    let overwrite = RewriteDiff::compute(&current, &desired, true);
+    assert_eq!(overwrite.add, vec![rewrite("tv.lan", "192.168.1.30")]);
+    assert_eq!(
+        overwrite.update,
+        vec![(
+            rewrite("printer.lan", "192.168.1.20"),
+            rewrite("printer.lan", "192.168.1.21")
+        )]
+    );
+    assert_eq!(overwrite.remove, vec![rewrite("old.lan", "192.168.1.99")]);
+    assert_eq!(overwrite.unchanged, 1);
+    assert!(
+        overwrite
+            .to_string()
+            .contains("~ printer.lan: 192.168.1.20 -> 192.168.1.21")
+    );
+    assert!(RewriteDiff::compute(&current, &current, true).is_empty());
+}
+
+#[tokio::test]
+async fn test_update_rewrite_legacy_fallback() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "version": "v0.107.20",
+            "language": "en",
+            "protection_enabled": true
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/control/rewrite/add"))
+        .and(wiremock::matchers::body_json(
+            serde_json::json!({ "domain": "nas.lan", "answer": "192.168.1.11" }),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/control/rewrite/delete"))
+        .and(wiremock::matchers::body_json(
+            serde_json::json!({ "domain": "nas.lan", "answer": "192.168.1.10" }),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client
+        .update_rewrite(
+            DnsRewrite {
+                domain: "nas.lan".to_string(),
+                answer: "192.168.1.10".to_string(),
+                extra: Default::default(),
+            },
+            DnsRewrite {
+                domain: "nas.lan".to_string(),
+                answer: "192.168.1.11".to_string(),
+                extra: Default::default(),
+            },
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_update_rewrite_noop_keeps_rewrite_on_legacy_server() {
+    let server = MockServer::start().await;
+    let client = AdGuardClient::new(crate::config::InstanceConfig {
+        url: server.uri(),
+        ..Default::default()
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/control/status"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "version": "v0.107.20",
+            "language": "en",
+            "protection_enabled": true
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/control/rewrite/add"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/control/rewrite/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let rewrite = DnsRewrite {
+        domain: "nas.lan".to_string(),
+        answer: "192.168.1.10".to_string(),
+        extra: Default::default(),
+    };
+    client
+        .update_rewrite(rewrite.clone(), rewrite)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_rewrite_analysis() {
+    use crate::adguard::rewrite_analysis::{analyze_rewrites, resolve};
+
+    let rewrite = |domain: &str, answer: &str| DnsRewrite {
+        domain: domain.to_string(),
+        answer: answer.to_string(),
+        extra: Default::default(),
+    };
+    let rewrites = vec![
+        rewrite("*.lan", "192.168.1.5"),
+        rewrite("nas.lan", "192.168.1.10"),
+        rewrite("nas.lan", "192.168.1.10"),
+        rewrite("*.home.lan", "192.168.1.5"),
+        rewrite("a.lan", "b.lan"),
+        rewrite("b.lan", "a.lan"),
+        rewrite("www.lan", "nas.lan"),
+        rewrite("www.lan", "192.168.1.11"),
+        rewrite("old.lan", "10.9.0.4"),
+    ];
+    let dhcp: DhcpStatus = serde_json::from_value(serde_json::json!({
+        "enabled": true, "interface_name": "eth0",
+        "v4": { "gateway_ip": "192.168.1.1", "subnet_mask": "255.255.255.0" },
+        "leases": [],
+        "static_leases": [{ "mac": "00:11:32:00:00:01", "ip": "192.168.1.12", "hostname": "nas" }]
+    }))
+    .unwrap();
+
+    let analysis = analyze_rewrites(&rewrites, Some(&dhcp), &[]);
+    assert_eq!(
+        analysis.duplicates,
+        vec![rewrite("nas.lan", "192.168.1.10")]
+    );
+    assert_eq!(analysis.conflicts, vec!["www.lan"]);
+    assert!(
+        analysis
+            .wildcard_overlaps
+            .iter()
+            .any(|o| o.wildcard.domain == "*.lan"
+                && o.winner.domain == "*.home.lan"
+                && o.same_answer)
+    );
+    assert_eq!(analysis.cname_loops, vec![vec!["a.lan", "b.lan", "a.lan"]]);
+    assert_eq!(analysis.dhcp_overlaps.len(), 2);
+    assert_eq!(analysis.dhcp_overlaps[0].lease_ip, "192.168.1.12");
+    assert_eq!(analysis.stale_answers, vec![rewrite("old.lan", "10.9.0.4")]);
+
+    let winner = resolve(&rewrites, "tv.home.lan");
+    assert_eq!(winner.chain, vec![rewrite("*.home.lan", "192.168.1.5")]);
+    assert_eq!(winner.answers, vec!["192.168.1.5"]);
+
+    let cname = resolve(&rewrites, "www.lan");
+    assert_eq!(cname.chain[0], rewrite("www.lan", "nas.lan"));
+    assert_eq!(cname.answers, vec!["192.168.1.10", "192.168.1.10"]);
+    assert!(resolve(&rewrites, "a.lan").looped);
+    assert!(resolve(&rewrites, "example.com").chain.is_empty());
+}
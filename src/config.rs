@@ -1,6 +1,7 @@
 use clap::ArgMatches;
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
@@ -25,12 +26,32 @@ pub struct AppConfig {
     pub no_verify_ssl: bool,
     #[serde(default, deserialize_with = "deserialize_instances")]
     pub instances: Vec<InstanceConfig>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_replicas")]
     pub replicas: Vec<ReplicaConfig>,
     #[serde(default = "default_sync_interval")]
     pub sync_interval_seconds: u64,
     #[serde(default = "default_sync_mode")]
     pub default_sync_mode: String,
+    /// Maximum number of replicas synced at the same time.
+    #[serde(default = "default_sync_concurrency")]
+    pub sync_concurrency: usize,
+    /// Random delay of up to this many seconds added before each background sync.
+    #[serde(default)]
+    pub sync_jitter_seconds: u64,
+    /// Poll the master this often and push changed modules right away; 0 disables.
+    #[serde(default)]
+    pub sync_poll_seconds: u64,
+    /// Sync outcomes kept per replica.
+    #[serde(default = "default_sync_history_size")]
+    pub sync_history_size: usize,
+    /// File the sync history is saved to; kept in memory only when unset.
+    pub sync_history_path: Option<String>,
+    /// Sync history shared by background sync and tools, opened by `load`.
+    #[serde(skip)]
+    pub sync_history: crate::sync::SyncHistoryStore,
+    /// Clients reused across tool calls, shared by every clone of the config.
+    #[serde(skip)]
+    pub clients: crate::adguard::ClientCache,
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
@@ -41,12 +62,57 @@ pub struct InstanceConfig {
     pub username: Option<String>,
     pub password: Option<String>,
     pub no_verify_ssl: Option<bool>,
+    /// Extra static headers sent with every request (e.g. reverse proxy service tokens).
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// PEM bundle of additional trusted root certificates.
+    pub ca_cert_path: Option<String>,
+    /// PEM client certificate for mTLS. May also contain the private key.
+    pub client_cert_path: Option<String>,
+    /// PEM private key for mTLS, if not bundled with the client certificate.
+    pub client_key_path: Option<String>,
+    /// HTTP(S) or SOCKS5 proxy URL (e.g. `socks5://127.0.0.1:1080`).
+    pub proxy: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
 pub struct ReplicaConfig {
+    /// Name of an `instances` entry to sync to, using its URL, credentials and TLS settings.
+    /// A bare string in the `replicas` list is shorthand for this.
+    pub instance: Option<String>,
+    /// Legacy: replica URL, authenticated with `api_key`. Ignored when `instance` is set.
+    #[serde(default)]
     pub url: String,
+    #[serde(default)]
     pub api_key: String,
+    /// Sync only these modules (see `sync::SYNC_MODULES`); empty means all.
+    #[serde(default)]
+    pub include_modules: Vec<String>,
+    /// Modules never pushed to this replica. Defaults to DHCP, TLS and profile.
+    #[serde(default)]
+    pub exclude_modules: Option<Vec<String>>,
+    /// Overrides `default_sync_mode` for this replica.
+    pub mode: Option<String>,
+    /// Overrides `sync_interval_seconds` for this replica.
+    pub sync_interval_seconds: Option<u64>,
+    /// Snapshot the replica before each push and restore it if a critical module fails.
+    #[serde(default)]
+    pub transactional: bool,
+    /// Modules whose failure triggers a rollback in transactional mode; empty means all.
+    #[serde(default)]
+    pub critical_modules: Vec<String>,
+}
+
+impl ReplicaConfig {
+    /// Instance name for named replicas, otherwise the URL.
+    pub fn name(&self) -> &str {
+        self.instance.as_deref().unwrap_or(&self.url)
+    }
+
+    /// Modules that trigger a rollback when they fail, with no default exclusions.
+    pub fn critical_selection(&self) -> Result<crate::sync::ModuleSelection, String> {
+        crate::sync::ModuleSelection::new(self.critical_modules.clone(), Some(Vec::new()))
+    }
 }
 
 fn default_transport() -> String {
@@ -77,6 +143,16 @@ fn default_sync_mode() -> String {
     "additive-merge".to_string()
 }
 
+fn default_sync_concurrency() -> usize {
+    4
+}
+
+fn default_sync_history_size() -> usize {
+    crate::sync::history::DEFAULT_HISTORY_SIZE
+}
+
+const SYNC_MODES: [&str; 2] = ["additive-merge", "full-overwrite"];
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -94,6 +170,13 @@ impl Default for AppConfig {
             replicas: Vec::new(),
             sync_interval_seconds: 3600,
             default_sync_mode: "additive-merge".to_string(),
+            sync_concurrency: 4,
+            sync_jitter_seconds: 0,
+            sync_poll_seconds: 0,
+            sync_history_size: default_sync_history_size(),
+            sync_history_path: None,
+            sync_history: Default::default(),
+            clients: Default::default(),
         }
     }
 }
@@ -142,6 +225,29 @@ where
     deserializer.deserialize_any(InstancesVisitor)
 }
 
+fn deserialize_replicas<'de, D>(deserializer: D) -> Result<Vec<ReplicaConfig>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ReplicaEntry {
+        Instance(String),
+        Config(ReplicaConfig),
+    }
+
+    Ok(Vec::<ReplicaEntry>::deserialize(deserializer)?
+        .into_iter()
+        .map(|entry| match entry {
+            ReplicaEntry::Instance(name) => ReplicaConfig {
+                instance: Some(name),
+                ..Default::default()
+            },
+            ReplicaEntry::Config(config) => config,
+        })
+        .collect())
+}
+
 impl AppConfig {
     pub fn load(file_path: Option<String>, cli_args: Vec<String>) -> Result<Self, ConfigError> {
         let mut builder = Config::builder();
@@ -162,7 +268,11 @@ impl AppConfig {
             .set_default("log_level", "info")?
             .set_default("no_verify_ssl", true)?
             .set_default("sync_interval_seconds", 3600)?
-            .set_default("default_sync_mode", "additive-merge")?;
+            .set_default("default_sync_mode", "additive-merge")?
+            .set_default("sync_concurrency", 4)?
+            .set_default("sync_jitter_seconds", 0)?
+            .set_default("sync_poll_seconds", 0)?
+            .set_default("sync_history_size", default_sync_history_size() as u64)?;
 
         // 3. Load from File
         if let Some(path) = path_to_load {
@@ -230,6 +340,10 @@ impl AppConfig {
 
         let mut config: AppConfig = builder.build()?.try_deserialize()?;
         config.validate().map_err(ConfigError::Message)?;
+        config.sync_history = crate::sync::SyncHistoryStore::open(
+            config.sync_history_path.as_ref().map(Into::into),
+            config.sync_history_size,
+        );
         Ok(config)
     }
 
@@ -250,6 +364,7 @@ impl AppConfig {
                     password: self.adguard_password.clone(),
                     no_verify_ssl: Some(self.no_verify_ssl),
                     api_key: None,
+                    ..Default::default()
                 });
             }
         }
@@ -258,10 +373,57 @@ impl AppConfig {
             return Err("At least one AdGuard Home instance must be configured".to_string());
         }
 
+        if !SYNC_MODES.contains(&self.default_sync_mode.as_str()) {
+            return Err(format!("Unknown sync mode: {}", self.default_sync_mode));
+        }
+        if self.sync_interval_seconds == 0 {
+            return Err("sync_interval_seconds must be greater than 0".to_string());
+        }
+        if self.sync_concurrency == 0 {
+            return Err("sync_concurrency must be greater than 0".to_string());
+        }
+
+        for replica in &self.replicas {
+            let instance = self.replica_instance(replica)?;
+            // Build the client now, so bad TLS or proxy settings fail at startup
+            self.clients
+                .get(&instance)
+                .map_err(|e| format!("Replica {}: {}", replica.name(), e))?;
+            if let Some(mode) = &replica.mode
+                && !SYNC_MODES.contains(&mode.as_str())
+            {
+                return Err(format!(
+                    "Replica {}: unknown sync mode {}",
+                    replica.name(),
+                    mode
+                ));
+            }
+            if replica.sync_interval_seconds == Some(0) {
+                return Err(format!(
+                    "Replica {}: sync_interval_seconds must be greater than 0",
+                    replica.name()
+                ));
+            }
+            crate::sync::ModuleSelection::new(
+                replica.include_modules.clone(),
+                replica.exclude_modules.clone(),
+            )
+            .map_err(|e| format!("Replica {}: {}", replica.name(), e))?;
+            replica
+                .critical_selection()
+                .map_err(|e| format!("Replica {}: critical_modules: {}", replica.name(), e))?;
+        }
+
         for (i, inst) in self.instances.iter().enumerate() {
             if inst.url.is_empty() {
                 return Err(format!("Instance {} is missing URL", i));
             }
+            if inst.client_key_path.is_some() && inst.client_cert_path.is_none() {
+                return Err(format!(
+                    "Instance {} has client_key_path without client_cert_path",
+                    i
+                ));
+            }
             // Ensure some form of auth is present, although it's not strictly required by AGH itself
             // it's highly recommended and expected by this MCP server.
         }
@@ -295,6 +457,50 @@ impl AppConfig {
             }
         }
     }
+
+    pub fn replica_mode<'a>(&'a self, replica: &'a ReplicaConfig) -> &'a str {
+        replica.mode.as_deref().unwrap_or(&self.default_sync_mode)
+    }
+
+    pub fn replica_interval(&self, replica: &ReplicaConfig) -> u64 {
+        replica
+            .sync_interval_seconds
+            .unwrap_or(self.sync_interval_seconds)
+    }
+
+    /// Connection settings for a replica: a copy of the named instance, or an
+    /// API-key instance built from a legacy `url`/`api_key` entry.
+    pub fn replica_instance(&self, replica: &ReplicaConfig) -> Result<InstanceConfig, String> {
+        match &replica.instance {
+            Some(name) => {
+                let instance = self
+                    .instances
+                    .iter()
+                    .find(|i| i.name.as_deref() == Some(name.as_str()))
+                    .ok_or_else(|| format!("Replica {}: no instance with that name", name))?;
+                if self.instances.first() == Some(instance) {
+                    return Err(format!(
+                        "Replica {}: the master instance cannot be its own replica",
+                        name
+                    ));
+                }
+                Ok(instance.clone())
+            }
+            None => {
+                if replica.url.is_empty() {
+                    return Err("Replica needs either an instance name or a URL".to_string());
+                }
+                url::Url::parse(&replica.url)
+                    .map_err(|e| format!("Replica {}: {}", replica.url, e))?;
+                Ok(InstanceConfig {
+                    name: Some(replica.url.clone()),
+                    url: replica.url.clone(),
+                    api_key: (!replica.api_key.is_empty()).then(|| replica.api_key.clone()),
+                    ..Default::default()
+                })
+            }
+        }
+    }
 }
 
 fn parse_args(args: Vec<String>) -> ArgMatches {
@@ -419,6 +625,8 @@ mod tests {
         assert_eq!(config.mcp_transport, "http");
         assert_eq!(config.http_port, 8080);
         assert!(config.lazy_mode);
+        // Sync history is only written to disk when a path is configured
+        assert_eq!(config.sync_history_path, None);
     }
 
     #[test]
@@ -529,6 +737,109 @@ api_key = "key2"
         assert_eq!(config.default_sync_mode, "full-overwrite");
     }
 
+    #[test]
+    fn test_named_replica_loading() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        writeln!(
+            file,
+            r#"
+adguard_host = "master.com"
+replicas = ["garage", {{ instance = "office", exclude_modules = ["clients"] }}]
+
+[[instances]]
+name = "primary"
+url = "http://192.168.1.1"
+
+[[instances]]
+name = "garage"
+url = "https://192.168.1.2"
+username = "admin"
+password = "secret"
+no_verify_ssl = false
+
+[[instances]]
+name = "office"
+url = "http://192.168.1.3"
+api_key = "office-key"
+"#
+        )
+        .unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+
+        let mut config = AppConfig::load(Some(path), vec![]).unwrap();
+        assert_eq!(config.replicas.len(), 2);
+        assert_eq!(config.replicas[0].name(), "garage");
+        assert_eq!(
+            config.replicas[1].exclude_modules,
+            Some(vec!["clients".to_string()])
+        );
+
+        let garage = config.replica_instance(&config.replicas[0]).unwrap();
+        assert_eq!(garage.url, "https://192.168.1.2");
+        assert_eq!(garage.username.as_deref(), Some("admin"));
+        assert_eq!(garage.no_verify_ssl, Some(false));
+        let office = config.replica_instance(&config.replicas[1]).unwrap();
+        assert_eq!(office.api_key.as_deref(), Some("office-key"));
+
+        config.replicas.push(ReplicaConfig {
+            instance: Some("attic".to_string()),
+            ..Default::default()
+        });
+        assert!(config.validate().unwrap_err().contains("no instance"));
+        config.replicas.pop();
+        config.replicas.push(ReplicaConfig {
+            instance: Some("primary".to_string()),
+            ..Default::default()
+        });
+        assert!(config.validate().unwrap_err().contains("own replica"));
+        config.replicas.pop();
+
+        // A replica whose connection settings cannot be built fails at startup
+        config.instances[1].ca_cert_path = Some("/nonexistent/ca.pem".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.starts_with("Replica garage:"), "{}", err);
+    }
+
+    #[test]
+    fn test_replica_sync_overrides() {
+        let mut config = AppConfig {
+            sync_interval_seconds: 600,
+            replicas: vec![
+                ReplicaConfig {
+                    url: "http://replica1.com".to_string(),
+                    mode: Some("full-overwrite".to_string()),
+                    sync_interval_seconds: Some(60),
+                    ..Default::default()
+                },
+                ReplicaConfig {
+                    url: "http://replica2.com".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        config.validate().unwrap();
+        assert_eq!(config.replica_mode(&config.replicas[0]), "full-overwrite");
+        assert_eq!(config.replica_interval(&config.replicas[0]), 60);
+        assert_eq!(config.replica_mode(&config.replicas[1]), "additive-merge");
+        assert_eq!(config.replica_interval(&config.replicas[1]), 600);
+
+        config.replicas[1].mode = Some("mirror".to_string());
+        assert!(config.validate().unwrap_err().contains("unknown sync mode"));
+        config.replicas[1].mode = None;
+        config.replicas[1].sync_interval_seconds = Some(0);
+        assert!(config.validate().is_err());
+        config.replicas[1].sync_interval_seconds = None;
+        config.replicas[1].transactional = true;
+        config.replicas[1].critical_modules = vec!["dns".to_string(), "firewall".to_string()];
+        assert!(config.validate().unwrap_err().contains("critical_modules"));
+        config.replicas[1].critical_modules.pop();
+        config.validate().unwrap();
+        config.sync_concurrency = 0;
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_replica_env_loading() {
         let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -589,6 +900,51 @@ no_verify_ssl = false
         assert_eq!(config.instances[1].no_verify_ssl, Some(false));
     }
 
+    #[test]
+    fn test_instance_proxy_settings_loading() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        writeln!(
+            file,
+            r#"
+adguard_host = "primary.com"
+
+[[instances]]
+name = "remote"
+url = "https://proxy.example.com/adguard"
+ca_cert_path = "/etc/ssl/private-ca.pem"
+client_cert_path = "/etc/ssl/client.pem"
+client_key_path = "/etc/ssl/client.key"
+proxy = "socks5://127.0.0.1:1080"
+
+[instances.headers]
+CF-Access-Client-Id = "id"
+CF-Access-Client-Secret = "secret"
+"#
+        )
+        .unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+
+        let config = AppConfig::load(Some(path), vec![]).unwrap();
+        let inst = &config.instances[0];
+        assert_eq!(inst.url, "https://proxy.example.com/adguard");
+        assert_eq!(inst.headers.len(), 2);
+        assert_eq!(
+            inst.headers.get("CF-Access-Client-Id").map(|s| s.as_str()),
+            Some("id")
+        );
+        assert_eq!(
+            inst.ca_cert_path.as_deref(),
+            Some("/etc/ssl/private-ca.pem")
+        );
+        assert_eq!(
+            inst.client_cert_path.as_deref(),
+            Some("/etc/ssl/client.pem")
+        );
+        assert_eq!(inst.client_key_path.as_deref(), Some("/etc/ssl/client.key"));
+        assert_eq!(inst.proxy.as_deref(), Some("socks5://127.0.0.1:1080"));
+    }
+
     #[test]
     fn test_multi_instance_env_loading() {
         let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -634,5 +990,13 @@ no_verify_ssl = false
             ..Default::default()
         }];
         assert!(config.validate().is_err());
+
+        // Client key without a certificate
+        config.instances = vec![InstanceConfig {
+            url: "http://localhost".to_string(),
+            client_key_path: Some("/etc/ssl/client.key".to_string()),
+            ..Default::default()
+        }];
+        assert!(config.validate().is_err());
     }
 }
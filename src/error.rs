@@ -17,6 +17,9 @@ pub enum Error {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
     #[error("MCP error: {0:?}")]
     Mcp(crate::mcp::ResponseError),
 
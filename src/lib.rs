@@ -1,3 +1,6 @@
+// Tool input schemas are large `serde_json::json!` literals
+#![recursion_limit = "256"]
+
 pub mod adguard;
 pub mod config;
 pub mod error;
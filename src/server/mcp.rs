@@ -182,7 +182,7 @@ impl McpServer {
                     };
 
                     // 3. Create client for this instance
-                    let client = AdGuardClient::new(instance_config.clone());
+                    let client = self.client_for(instance_config)?;
 
                     let handler = {
                         let registry = self.registry.lock().unwrap();
@@ -292,6 +292,10 @@ impl McpServer {
         }
     }
 
+    fn client_for(&self, instance: &crate::config::InstanceConfig) -> Result<AdGuardClient> {
+        Ok(self.config.clients.get(instance)?)
+    }
+
     async fn send_notification(&self, method: &str, params: Option<Value>) -> Result<()> {
         let notification = Notification {
             jsonrpc: "2.0".to_string(),
@@ -1,14 +1,25 @@
 use crate::adguard::AdGuardClient;
 use crate::adguard::models::{
-    AccessList, AdGuardClientDevice, DhcpStatus, DnsConfig, DnsRewrite, FilteringConfig,
-    ParentalControlConfig, ProfileInfo, QueryLogConfig, SafeSearchConfig, TlsConfig,
+    AccessList, AdGuardClientDevice, DhcpConfig, DhcpStatus, DnsConfig, DnsRewrite, Filter,
+    FilteringConfig, ParentalControlConfig, ProfileInfo, QueryLogConfig, SafeSearchConfig,
+    TlsConfig,
 };
-use crate::config::{AppConfig, InstanceConfig};
+use crate::config::{AppConfig, ReplicaConfig};
 use anyhow::Result;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tokio::time::interval;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore, broadcast};
+use tokio::task::JoinSet;
+use tokio::time::{MissedTickBehavior, interval};
+
+pub mod history;
+
+pub use history::{Drift, SyncHistoryStore, SyncRecord};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BackupMetadata {
@@ -17,7 +28,7 @@ pub struct BackupMetadata {
     pub description: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SyncState {
     pub metadata: Option<BackupMetadata>,
     pub filtering: FilteringConfig,
@@ -35,12 +46,367 @@ pub struct SyncState {
     pub profile_info: ProfileInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SyncResult {
     pub success: bool,
     pub applied_modules: Vec<String>,
     pub failed_modules: Vec<String>,
+    /// Modules left out by the replica's include/exclude lists.
+    #[serde(default)]
+    pub skipped_modules: Vec<String>,
     pub errors: Vec<String>,
+    /// Restore of the pre-sync snapshot after a transactional push failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback: Option<Box<SyncResult>>,
+}
+
+impl SyncResult {
+    /// Whether a rollback touched `module`. A failed restore counts too: the
+    /// replica no longer holds what was pushed.
+    pub fn rolled_back(&self, module: &str) -> bool {
+        self.rollback.as_ref().is_some_and(|rollback| {
+            rollback
+                .applied_modules
+                .iter()
+                .chain(&rollback.failed_modules)
+                .any(|m| m == module)
+        })
+    }
+}
+
+/// Sync modules in push order: config key and display name.
+pub const SYNC_MODULES: [(&str, &str); 16] = [
+    ("user_rules", "User Rules"),
+    ("filter_lists", "Filter Lists"),
+    ("allowlists", "Allowlists"),
+    ("filter_interval", "Filter Update Interval"),
+    ("clients", "Clients"),
+    ("blocked_services", "Blocked Services"),
+    ("rewrites", "DNS Rewrites"),
+    ("dns", "DNS Config"),
+    ("access_list", "Access List"),
+    ("query_log", "Query Log Config"),
+    ("safe_search", "Safe Search"),
+    ("parental_control", "Parental Control"),
+    ("protection", "Global Protection"),
+    ("dhcp", "DHCP Config"),
+    ("tls", "TLS Config"),
+    ("profile", "Profile Info"),
+];
+
+/// Config key of the module with display name `name`.
+pub fn module_key(name: &str) -> Option<&'static str> {
+    SYNC_MODULES
+        .iter()
+        .find(|(_, display)| *display == name)
+        .map(|(key, _)| *key)
+}
+
+/// Host-specific modules a replica does not get unless asked for.
+pub const DEFAULT_EXCLUDED_MODULES: [&str; 3] = ["dhcp", "tls", "profile"];
+
+/// Which modules a push applies. An include list wins over the default
+/// exclusions; an explicit exclude list replaces them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleSelection {
+    include: Vec<String>,
+    exclude: Option<Vec<String>>,
+}
+
+impl ModuleSelection {
+    pub fn new(include: Vec<String>, exclude: Option<Vec<String>>) -> Result<Self, String> {
+        for module in include.iter().chain(exclude.iter().flatten()) {
+            if !SYNC_MODULES.iter().any(|(key, _)| key == module) {
+                return Err(format!(
+                    "Unknown sync module '{}' (expected one of: {})",
+                    module,
+                    SYNC_MODULES.map(|(key, _)| key).join(", ")
+                ));
+            }
+        }
+        Ok(Self { include, exclude })
+    }
+
+    /// Narrows this selection to `modules`, or `None` if none of them are allowed.
+    pub fn only(&self, modules: &[String]) -> Option<Self> {
+        let include: Vec<String> = SYNC_MODULES
+            .iter()
+            .filter(|(key, _)| self.allows(key) && modules.iter().any(|m| m == key))
+            .map(|(key, _)| key.to_string())
+            .collect();
+        (!include.is_empty()).then_some(Self {
+            include,
+            exclude: Some(Vec::new()),
+        })
+    }
+
+    pub fn allows(&self, module: &str) -> bool {
+        let excluded = match &self.exclude {
+            Some(exclude) => exclude.iter().any(|m| m == module),
+            None => self.include.is_empty() && DEFAULT_EXCLUDED_MODULES.contains(&module),
+        };
+        let included = self.include.is_empty() || self.include.iter().any(|m| m == module);
+        included && !excluded
+    }
+}
+
+/// A setting a push would change on the replica.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// What a push would do to one module of a replica.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ModulePlan {
+    pub module: String,
+    pub add: Vec<String>,
+    pub remove: Vec<String>,
+    pub changes: Vec<FieldChange>,
+}
+
+impl ModulePlan {
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.remove.is_empty() && self.changes.is_empty()
+    }
+
+    fn diff_items(&mut self, current: &[String], desired: &[String], overwrite: bool) {
+        self.add = desired
+            .iter()
+            .filter(|item| !current.contains(item))
+            .cloned()
+            .collect();
+        if overwrite {
+            self.remove = current
+                .iter()
+                .filter(|item| !desired.contains(item))
+                .cloned()
+                .collect();
+        }
+    }
+
+    fn diff_filters(&mut self, current: &[Filter], desired: &[Filter], overwrite: bool) {
+        let label = |f: &Filter| format!("{} ({})", f.name, f.url);
+        for filter in desired {
+            match current.iter().find(|f| f.url == filter.url) {
+                None => self.add.push(label(filter)),
+                Some(existing) => self.changes.extend(field_changes(
+                    &filter.url,
+                    &json!({"name": existing.name, "enabled": existing.enabled}),
+                    &json!({"name": filter.name, "enabled": filter.enabled}),
+                )),
+            }
+        }
+        if overwrite {
+            self.remove = current
+                .iter()
+                .filter(|f| !desired.iter().any(|d| d.url == f.url))
+                .map(label)
+                .collect();
+        }
+    }
+}
+
+/// Top-level fields of `new` that differ from `old`, named `prefix.field`.
+/// Fields only `old` has are left alone by a push, so they are not reported.
+fn field_changes<T: Serialize>(prefix: &str, old: &T, new: &T) -> Vec<FieldChange> {
+    let old = serde_json::to_value(old).unwrap_or_default();
+    let new = serde_json::to_value(new).unwrap_or_default();
+    let name = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+    match (&old, &new) {
+        (Value::Object(old_fields), Value::Object(new_fields)) => new_fields
+            .iter()
+            .filter(|(key, value)| old_fields.get(*key).unwrap_or(&Value::Null) != *value)
+            .map(|(key, value)| FieldChange {
+                field: name(key),
+                old: old_fields.get(key).cloned().unwrap_or_default(),
+                new: value.clone(),
+            })
+            .collect(),
+        _ if old != new => vec![FieldChange {
+            field: prefix.to_string(),
+            old,
+            new,
+        }],
+        _ => Vec::new(),
+    }
+}
+
+/// Per-module changes a push would make to one replica, computed without applying anything.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SyncPlan {
+    pub mode: String,
+    pub modules: Vec<ModulePlan>,
+    pub unchanged_modules: Vec<String>,
+    pub skipped_modules: Vec<String>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+}
+
+impl std::fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            writeln!(f, "Mode {}: replica already matches the master.", self.mode)?;
+        } else {
+            writeln!(
+                f,
+                "Mode {}: {} module(s) would change.",
+                self.mode,
+                self.modules.len()
+            )?;
+        }
+        for module in &self.modules {
+            writeln!(f, "{}:", module.module)?;
+            for item in &module.add {
+                writeln!(f, "  + {}", item)?;
+            }
+            for item in &module.remove {
+                writeln!(f, "  - {}", item)?;
+            }
+            for change in &module.changes {
+                writeln!(f, "  ~ {}: {} -> {}", change.field, change.old, change.new)?;
+            }
+        }
+        if !self.unchanged_modules.is_empty() {
+            writeln!(f, "Unchanged: {}", self.unchanged_modules.join(", "))?;
+        }
+        if !self.skipped_modules.is_empty() {
+            writeln!(
+                f,
+                "Skipped by selection: {}",
+                self.skipped_modules.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Replicas due at about the same time share one fetch of the master state.
+const MASTER_STATE_MAX_AGE: Duration = Duration::from_secs(30);
+
+struct MasterState {
+    client: AdGuardClient,
+    cached: Mutex<Option<(Instant, Arc<SyncState>)>>,
+}
+
+impl MasterState {
+    async fn state(&self) -> Result<Arc<SyncState>> {
+        let mut cached = self.cached.lock().await;
+        if let Some((fetched, state)) = cached.as_ref()
+            && fetched.elapsed() < MASTER_STATE_MAX_AGE
+        {
+            return Ok(state.clone());
+        }
+        let state = Arc::new(SyncState::fetch(&self.client).await?);
+        *cached = Some((Instant::now(), state.clone()));
+        Ok(state)
+    }
+
+    /// Fetches the master state even if a recent copy is cached.
+    async fn refresh(&self) -> Result<Arc<SyncState>> {
+        let mut cached = self.cached.lock().await;
+        let state = Arc::new(SyncState::fetch(&self.client).await?);
+        *cached = Some((Instant::now(), state.clone()));
+        Ok(state)
+    }
+}
+
+/// Modules that changed on the master between two polls, with the new state.
+#[derive(Debug)]
+struct MasterChange {
+    state: Arc<SyncState>,
+    modules: Vec<String>,
+}
+
+/// Waits for the next master change; never resolves once polling is off.
+async fn next_change(
+    changes: &mut Option<broadcast::Receiver<Arc<MasterChange>>>,
+) -> std::result::Result<Arc<MasterChange>, broadcast::error::RecvError> {
+    match changes {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 64-bit FNV-1a. Fingerprints are saved in the sync history, so unlike std's
+/// `DefaultHasher` the result must stay the same across Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Random delay below `max_seconds`, spreading replica syncs apart.
+fn jitter(max_seconds: u64) -> Duration {
+    if max_seconds == 0 {
+        return Duration::ZERO;
+    }
+    let random = RandomState::new().hash_one(Instant::now());
+    Duration::from_millis(random % (max_seconds * 1000))
+}
+
+/// Adds, updates or (in full-overwrite mode) removes replica filter lists of one
+/// kind so they match the master, keyed by URL.
+async fn sync_filter_lists(
+    client: &AdGuardClient,
+    master: &[Filter],
+    replica: &[Filter],
+    whitelist: bool,
+    mode: &str,
+) -> Result<()> {
+    for filter in master {
+        match replica.iter().find(|f| f.url == filter.url) {
+            None => {
+                client
+                    .add_filter(filter.name.clone(), filter.url.clone(), whitelist)
+                    .await?;
+                // Lists are added enabled
+                if !filter.enabled {
+                    client
+                        .update_filter(
+                            filter.url.clone(),
+                            filter.url.clone(),
+                            filter.name.clone(),
+                            whitelist,
+                            false,
+                        )
+                        .await?;
+                }
+            }
+            Some(current) if current.name != filter.name || current.enabled != filter.enabled => {
+                client
+                    .update_filter(
+                        filter.url.clone(),
+                        filter.url.clone(),
+                        filter.name.clone(),
+                        whitelist,
+                        filter.enabled,
+                    )
+                    .await?;
+            }
+            Some(_) => {}
+        }
+    }
+    if mode == "full-overwrite" {
+        for current in replica {
+            if !master.iter().any(|f| f.url == current.url) {
+                client.remove_filter(current.url.clone(), whitelist).await?;
+            }
+        }
+    }
+    Ok(())
 }
 
 impl SyncState {
@@ -50,45 +416,201 @@ impl SyncState {
             return;
         }
 
-        let mut interval = interval(Duration::from_secs(config.sync_interval_seconds));
-        let master_instance = config
-            .get_instance(None)
-            .expect("No instances configured")
-            .clone();
-        let master_client = AdGuardClient::new(master_instance);
+        let master_instance = config.get_instance(None).expect("No instances configured");
+        let master_client = match config.clients.get(master_instance) {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("Master {}: {}", master_instance.url, e);
+                return;
+            }
+        };
+        let master = Arc::new(MasterState {
+            client: master_client,
+            cached: Mutex::new(None),
+        });
+        let permits = Arc::new(Semaphore::new(config.sync_concurrency));
+        let config = Arc::new(config);
+
+        let mut tasks = JoinSet::new();
+        let changes = (config.sync_poll_seconds > 0).then(|| {
+            let (sender, _) = broadcast::channel(16);
+            tasks.spawn(Self::watch_master(
+                master.clone(),
+                Duration::from_secs(config.sync_poll_seconds),
+                sender.clone(),
+            ));
+            sender
+        });
+
+        // Each replica runs on its own schedule, so a slow one never holds up the rest
+        for replica in config.replicas.clone() {
+            tasks.spawn(Self::run_replica_sync(
+                config.clone(),
+                replica,
+                master.clone(),
+                permits.clone(),
+                changes.as_ref().map(|sender| sender.subscribe()),
+            ));
+        }
+        while tasks.join_next().await.is_some() {}
+    }
 
+    /// Polls the master and announces which modules changed since the last poll.
+    async fn watch_master(
+        master: Arc<MasterState>,
+        poll: Duration,
+        changes: broadcast::Sender<Arc<MasterChange>>,
+    ) {
+        let mut interval = interval(poll);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut previous: Option<BTreeMap<String, u64>> = None;
         loop {
             interval.tick().await;
-            tracing::info!("Starting background synchronization...");
-
-            match Self::fetch(&master_client).await {
-                Ok(state) => {
-                    for replica in &config.replicas {
-                        let url = replica.url.clone();
-                        match url::Url::parse(&url) {
-                            Ok(_parsed_url) => {
-                                let replica_instance = InstanceConfig {
-                                    name: Some("replica".to_string()),
-                                    url: url.clone(),
-                                    api_key: Some(replica.api_key.clone()),
-                                    ..Default::default()
-                                };
-
-                                let replica_client = AdGuardClient::new(replica_instance);
-                                if let Err(e) = state
-                                    .push_to_replica(&replica_client, &config.default_sync_mode)
-                                    .await
-                                {
-                                    tracing::error!("Failed to sync to replica {}: {}", url, e);
-                                } else {
-                                    tracing::info!("Successfully synced to replica {}", url);
-                                }
-                            }
-                            Err(e) => tracing::error!("Failed to parse replica URL {}: {}", url, e),
-                        }
+            let state = match master.refresh().await {
+                Ok(state) => state,
+                Err(e) => {
+                    tracing::warn!("Failed to poll master state: {}", e);
+                    continue;
+                }
+            };
+            let fingerprints = state.fingerprints();
+            if let Some(previous) = &previous {
+                let modules: Vec<String> = fingerprints
+                    .iter()
+                    .filter(|(module, hash)| previous.get(*module) != Some(hash))
+                    .map(|(module, _)| module.clone())
+                    .collect();
+                if !modules.is_empty() {
+                    tracing::info!("Master changed: {}", modules.join(", "));
+                    // No receivers only means no replica is listening yet
+                    let _ = changes.send(Arc::new(MasterChange { state, modules }));
+                }
+            }
+            previous = Some(fingerprints);
+        }
+    }
+
+    async fn run_replica_sync(
+        config: Arc<AppConfig>,
+        replica: ReplicaConfig,
+        master: Arc<MasterState>,
+        permits: Arc<Semaphore>,
+        mut changes: Option<broadcast::Receiver<Arc<MasterChange>>>,
+    ) {
+        let name = replica.name();
+        // Proxy, CA and client certificate errors stop the replica rather than
+        // sending its credentials over a default connection
+        let replica_client = match config.replica_instance(&replica).and_then(|instance| {
+            config
+                .clients
+                .get(&instance)
+                .map_err(|e| format!("Replica {}: {}", name, e))
+        }) {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return;
+            }
+        };
+        let selection = ModuleSelection::new(
+            replica.include_modules.clone(),
+            replica.exclude_modules.clone(),
+        )
+        .unwrap_or_default();
+        let critical = replica
+            .transactional
+            .then(|| replica.critical_selection().unwrap_or_default());
+        let mode = config.replica_mode(&replica);
+        let period = Duration::from_secs(config.replica_interval(&replica));
+
+        let mut interval = interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            // The timer pushes everything; a master change pushes only what changed
+            let (changed_state, push_selection, trigger) = tokio::select! {
+                _ = interval.tick() => {
+                    tokio::time::sleep(jitter(config.sync_jitter_seconds)).await;
+                    (None, selection.clone(), "schedule")
+                }
+                change = next_change(&mut changes) => match change {
+                    Ok(change) => match selection.only(&change.modules) {
+                        Some(only) => (Some(change.state.clone()), only, "change"),
+                        None => continue,
+                    },
+                    // Missed some changes: fall back to a full push
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        (None, selection.clone(), "schedule")
                     }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        changes = None;
+                        continue;
+                    }
+                },
+            };
+            let Ok(_permit) = permits.acquire().await else {
+                return;
+            };
+            tracing::info!("Starting background synchronization to replica {}...", name);
+
+            let state = match changed_state {
+                Some(state) => state,
+                None => match master.state().await {
+                    Ok(state) => state,
+                    Err(e) => {
+                        tracing::error!("Failed to fetch master state for sync: {}", e);
+                        continue;
+                    }
+                },
+            };
+            // A plain sync never runs into the next one. A transactional sync
+            // only bounds its snapshot: once that is taken, the push and any
+            // rollback run to completion and the ticks they overrun are skipped.
+            let push = async {
+                match &critical {
+                    Some(critical) => {
+                        let snapshot =
+                            tokio::time::timeout(period, Self::snapshot(&replica_client))
+                                .await
+                                .map_err(|_| {
+                                    anyhow::anyhow!(
+                                        "Snapshot timed out after {:?}, nothing was pushed",
+                                        period
+                                    )
+                                })??;
+                        state
+                            .push_with_rollback(
+                                &replica_client,
+                                mode,
+                                &push_selection,
+                                critical,
+                                snapshot,
+                            )
+                            .await
+                    }
+                    None => tokio::time::timeout(
+                        period,
+                        state.push_selected(&replica_client, mode, &push_selection),
+                    )
+                    .await
+                    .map_err(|_| anyhow::anyhow!("Timed out after {:?}", period))?,
                 }
-                Err(e) => tracing::error!("Failed to fetch master state for sync: {}", e),
+            };
+            match config
+                .sync_history
+                .track(name, trigger, mode, state.fingerprints(), push)
+                .await
+            {
+                Ok(result) if result.success => tracing::info!(
+                    "Successfully synced to replica {}: {}",
+                    name,
+                    result.applied_modules.join(", ")
+                ),
+                Ok(result) => tracing::error!(
+                    "Synced to replica {} with errors: {}",
+                    name,
+                    result.errors.join("; ")
+                ),
+                Err(e) => tracing::error!("Failed to sync to replica {}: {}", name, e),
             }
         }
     }
@@ -136,18 +658,96 @@ impl SyncState {
         })
     }
 
+    /// `push_selected` as a transaction: the replica is snapshotted first, and
+    /// if a module allowed by `critical` fails, every module the push touched is
+    /// restored from the snapshot. Nothing is pushed if the snapshot fails.
+    pub async fn push_transactional(
+        &self,
+        client: &AdGuardClient,
+        mode: &str,
+        selection: &ModuleSelection,
+        critical: &ModuleSelection,
+    ) -> Result<SyncResult> {
+        let snapshot = Self::snapshot(client).await?;
+        self.push_with_rollback(client, mode, selection, critical, snapshot)
+            .await
+    }
+
+    async fn snapshot(client: &AdGuardClient) -> Result<Self> {
+        Self::fetch_full(client, Some("Pre-sync snapshot".to_string()))
+            .await
+            .map_err(|e| anyhow::anyhow!("Snapshot failed, nothing was pushed: {}", e))
+    }
+
+    /// The push half of `push_transactional`, restoring from an already taken
+    /// `snapshot`.
+    async fn push_with_rollback(
+        &self,
+        client: &AdGuardClient,
+        mode: &str,
+        selection: &ModuleSelection,
+        critical: &ModuleSelection,
+        snapshot: Self,
+    ) -> Result<SyncResult> {
+        let mut result = self.push_selected(client, mode, selection).await?;
+        let critical_failed = result
+            .failed_modules
+            .iter()
+            .any(|name| module_key(name).is_some_and(|key| critical.allows(key)));
+        if !critical_failed {
+            return Ok(result);
+        }
+
+        let touched = ModuleSelection {
+            include: result
+                .applied_modules
+                .iter()
+                .chain(&result.failed_modules)
+                .filter_map(|name| module_key(name))
+                .map(str::to_string)
+                .collect(),
+            exclude: Some(Vec::new()),
+        };
+        let rollback = snapshot
+            .push_selected(client, "full-overwrite", &touched)
+            .await
+            .unwrap_or_else(|e| SyncResult {
+                errors: vec![e.to_string()],
+                ..Default::default()
+            });
+        result.rollback = Some(Box::new(rollback));
+        Ok(result)
+    }
+
     pub async fn push_to_replica(&self, client: &AdGuardClient, mode: &str) -> Result<SyncResult> {
+        self.push_selected(client, mode, &ModuleSelection::default())
+            .await
+    }
+
+    pub async fn push_selected(
+        &self,
+        client: &AdGuardClient,
+        mode: &str,
+        selection: &ModuleSelection,
+    ) -> Result<SyncResult> {
         let mut applied = Vec::new();
         let mut failed = Vec::new();
         let mut errors = Vec::new();
+        let skipped: Vec<String> = SYNC_MODULES
+            .iter()
+            .filter(|(key, _)| !selection.allows(key))
+            .map(|(_, name)| name.to_string())
+            .collect();
 
         macro_rules! try_sync {
             ($module:expr, $op:expr) => {
-                match $op.await {
-                    Ok(_) => applied.push($module.to_string()),
-                    Err(e) => {
-                        failed.push($module.to_string());
-                        errors.push(format!("{}: {}", $module, e));
+                if !skipped.iter().any(|m| m == $module) {
+                    match $op.await {
+                        Ok(_) => applied.push($module.to_string()),
+                        Err(e) => {
+                            failed.push($module.to_string());
+                            errors.push(format!("{}: {}", $module, e));
+                        }
                     }
                 }
             };
@@ -171,7 +771,82 @@ impl SyncState {
             }
         });
 
-        // 2. Sync Blocked Services
+        // 2. Sync Filter Lists, Allowlists and the update interval
+        let filter_modules = ["Filter Lists", "Allowlists", "Filter Update Interval"];
+        let filter_modules: Vec<_> = filter_modules
+            .into_iter()
+            .filter(|m| !skipped.iter().any(|s| s == m))
+            .collect();
+        let existing_filters = if filter_modules.is_empty() {
+            None
+        } else {
+            Some(client.list_filters().await)
+        };
+        match existing_filters {
+            None => {}
+            Some(Ok(existing)) => {
+                try_sync!(
+                    "Filter Lists",
+                    sync_filter_lists(
+                        client,
+                        &self.filtering.filters,
+                        &existing.filters,
+                        false,
+                        mode
+                    )
+                );
+                try_sync!(
+                    "Allowlists",
+                    sync_filter_lists(
+                        client,
+                        &self.filtering.whitelist_filters,
+                        &existing.whitelist_filters,
+                        true,
+                        mode
+                    )
+                );
+                // The interval is a single value; the replica keeps its own enabled flag
+                try_sync!(
+                    "Filter Update Interval",
+                    client.set_filtering_config(existing.enabled, self.filtering.interval)
+                );
+            }
+            Some(Err(e)) => {
+                for module in filter_modules {
+                    failed.push(module.to_string());
+                    errors.push(format!("{}: {}", module, e));
+                }
+            }
+        }
+
+        // 3. Sync Persistent Clients
+        try_sync!("Clients", async {
+            let existing = client.list_clients().await?;
+            for device in &self.clients {
+                match existing.iter().find(|c| c.name == device.name) {
+                    None => client.add_client(device.clone()).await?,
+                    Some(current) => {
+                        if serde_json::to_value(current.settings())?
+                            != serde_json::to_value(device.settings())?
+                        {
+                            client
+                                .update_client(device.name.clone(), device.clone())
+                                .await?;
+                        }
+                    }
+                }
+            }
+            if mode == "full-overwrite" {
+                for current in &existing {
+                    if !self.clients.iter().any(|c| c.name == current.name) {
+                        client.delete_client(current.name.clone()).await?;
+                    }
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        });
+
+        // 4. Sync Blocked Services
         try_sync!("Blocked Services", async {
             if mode == "full-overwrite" {
                 client
@@ -189,7 +864,7 @@ impl SyncState {
             }
         });
 
-        // 3. Sync Rewrites
+        // 5. Sync Rewrites
         try_sync!("DNS Rewrites", async {
             let existing_rewrites = client.list_rewrites().await?;
             if mode == "full-overwrite" {
@@ -218,46 +893,46 @@ impl SyncState {
             Ok::<(), anyhow::Error>(())
         });
 
-        // 4. Sync DNS Config
+        // 6. Sync DNS Config
         try_sync!("DNS Config", client.set_dns_config(self.dns.clone()));
 
-        // 5. Sync Access List
+        // 7. Sync Access List
         try_sync!(
             "Access List",
             client.set_access_list(self.access_list.clone())
         );
 
-        // 6. Sync Query Log Config
+        // 8. Sync Query Log Config
         try_sync!(
             "Query Log Config",
             client.set_query_log_config(self.query_log_config.clone())
         );
 
-        // 7. Sync Safe Search
+        // 9. Sync Safe Search
         try_sync!(
             "Safe Search",
             client.set_safe_search_settings(self.safe_search.clone())
         );
 
-        // 8. Sync Parental Control
+        // 10. Sync Parental Control
         try_sync!(
             "Parental Control",
             client.set_parental_settings(self.parental_control.clone())
         );
 
-        // 9. Sync Protection
+        // 11. Sync Protection
         try_sync!(
             "Global Protection",
             client.set_protection(self.safe_browsing)
         );
 
-        // 10. Sync DHCP Config
+        // 12. Sync DHCP Config
         try_sync!("DHCP Config", client.set_dhcp_config(self.dhcp.clone()));
 
-        // 11. Sync TLS Config
+        // 13. Sync TLS Config
         try_sync!("TLS Config", client.configure_tls(self.tls.clone()));
 
-        // 12. Sync Profile Info
+        // 14. Sync Profile Info
         try_sync!(
             "Profile Info",
             client.set_profile_info(self.profile_info.clone())
@@ -268,10 +943,176 @@ impl SyncState {
             success,
             applied_modules: applied,
             failed_modules: failed,
+            skipped_modules: skipped,
             errors,
+            rollback: None,
         })
     }
 
+    /// Hash of each sync module's content, keyed like `SYNC_MODULES`. Values a
+    /// push never writes (filter list update times, DHCP leases) are left out,
+    /// so they do not count as changes.
+    pub fn fingerprints(&self) -> BTreeMap<String, u64> {
+        let lists = |filters: &[Filter]| -> Value {
+            filters
+                .iter()
+                .map(|f| json!([f.url, f.name, f.enabled]))
+                .collect()
+        };
+        SYNC_MODULES
+            .iter()
+            .map(|(key, _)| {
+                let content = match *key {
+                    "user_rules" => json!(self.filtering.user_rules),
+                    "filter_lists" => lists(&self.filtering.filters),
+                    "allowlists" => lists(&self.filtering.whitelist_filters),
+                    "filter_interval" => json!(self.filtering.interval),
+                    "clients" => json!(
+                        self.clients
+                            .iter()
+                            .map(AdGuardClientDevice::settings)
+                            .collect::<Vec<_>>()
+                    ),
+                    "blocked_services" => json!(self.blocked_services),
+                    "rewrites" => json!(self.rewrites),
+                    "dns" => json!(self.dns),
+                    "access_list" => json!(self.access_list),
+                    "query_log" => json!(self.query_log_config),
+                    "safe_search" => json!(self.safe_search),
+                    "parental_control" => json!(self.parental_control),
+                    "protection" => json!(self.safe_browsing),
+                    "dhcp" => json!(DhcpConfig::from(&self.dhcp)),
+                    "tls" => json!(self.tls),
+                    "profile" => json!(self.profile_info),
+                    _ => Value::Null,
+                };
+                // Objects serialize with sorted keys, so equal content hashes equally
+                (key.to_string(), fnv1a(content.to_string().as_bytes()))
+            })
+            .collect()
+    }
+
+    /// Works out, module by module, what `push_selected` would change on a
+    /// replica currently in state `replica`.
+    pub fn plan(&self, replica: &SyncState, mode: &str, selection: &ModuleSelection) -> SyncPlan {
+        let overwrite = mode == "full-overwrite";
+        let mut plan = SyncPlan {
+            mode: mode.to_string(),
+            ..Default::default()
+        };
+
+        for (key, name) in SYNC_MODULES {
+            if !selection.allows(key) {
+                plan.skipped_modules.push(name.to_string());
+                continue;
+            }
+            let mut module = ModulePlan {
+                module: name.to_string(),
+                ..Default::default()
+            };
+            match key {
+                "user_rules" => module.diff_items(
+                    &replica.filtering.user_rules,
+                    &self.filtering.user_rules,
+                    overwrite,
+                ),
+                "filter_lists" => module.diff_filters(
+                    &replica.filtering.filters,
+                    &self.filtering.filters,
+                    overwrite,
+                ),
+                "allowlists" => module.diff_filters(
+                    &replica.filtering.whitelist_filters,
+                    &self.filtering.whitelist_filters,
+                    overwrite,
+                ),
+                "filter_interval" => {
+                    module.changes = field_changes(
+                        "interval",
+                        &replica.filtering.interval,
+                        &self.filtering.interval,
+                    )
+                }
+                "clients" => {
+                    for device in &self.clients {
+                        match replica.clients.iter().find(|c| c.name == device.name) {
+                            None => module.add.push(device.name.clone()),
+                            Some(current) => module.changes.extend(field_changes(
+                                &device.name,
+                                &current.settings(),
+                                &device.settings(),
+                            )),
+                        }
+                    }
+                    if overwrite {
+                        module.remove = replica
+                            .clients
+                            .iter()
+                            .filter(|c| !self.clients.iter().any(|d| d.name == c.name))
+                            .map(|c| c.name.clone())
+                            .collect();
+                    }
+                }
+                "blocked_services" => {
+                    module.diff_items(&replica.blocked_services, &self.blocked_services, overwrite)
+                }
+                "rewrites" => {
+                    let label = |rewrites: &[DnsRewrite]| -> Vec<String> {
+                        rewrites
+                            .iter()
+                            .map(|r| format!("{} -> {}", r.domain, r.answer))
+                            .collect()
+                    };
+                    module.diff_items(&label(&replica.rewrites), &label(&self.rewrites), overwrite)
+                }
+                "dns" => module.changes = field_changes("", &replica.dns, &self.dns),
+                "access_list" => {
+                    module.changes = field_changes("", &replica.access_list, &self.access_list)
+                }
+                "query_log" => {
+                    module.changes =
+                        field_changes("", &replica.query_log_config, &self.query_log_config)
+                }
+                "safe_search" => {
+                    module.changes = field_changes("", &replica.safe_search, &self.safe_search)
+                }
+                "parental_control" => {
+                    module.changes =
+                        field_changes("", &replica.parental_control, &self.parental_control)
+                }
+                "protection" => {
+                    module.changes = field_changes(
+                        "protection_enabled",
+                        &replica.safe_browsing,
+                        &self.safe_browsing,
+                    )
+                }
+                "dhcp" => {
+                    module.changes = field_changes(
+                        "",
+                        &DhcpConfig::from(&replica.dhcp),
+                        &DhcpConfig::from(&self.dhcp),
+                    )
+                }
+                "tls" => {
+                    module.changes =
+                        field_changes("", &replica.tls.redacted(), &self.tls.redacted())
+                }
+                "profile" => {
+                    module.changes = field_changes("", &replica.profile_info, &self.profile_info)
+                }
+                _ => {}
+            }
+
+            if module.is_empty() {
+                plan.unchanged_modules.push(name.to_string());
+            } else {
+                plan.modules.push(module);
+            }
+        }
+        plan
+    }
+
     pub fn diff(&self, other: &Self) -> String {
         let mut changes = Vec::new();
 
@@ -372,6 +1213,7 @@ mod tests {
                 filters: Vec::new(),
                 whitelist_filters: Vec::new(),
                 user_rules: Vec::new(),
+                extra: Default::default(),
             },
             clients: Vec::new(),
             dns: DnsConfig {
@@ -389,6 +1231,7 @@ mod tests {
                 upstream_mode: "".to_string(),
                 use_private_ptr_resolvers: false,
                 local_ptr_upstreams: Vec::new(),
+                ..Default::default()
             },
             blocked_services: Vec::new(),
             rewrites: Vec::new(),
@@ -396,6 +1239,7 @@ mod tests {
                 allowed_clients: vec![],
                 disallowed_clients: vec![],
                 blocked_hosts: vec![],
+                extra: Default::default(),
             },
             query_log_config: QueryLogConfig {
                 enabled: true,
@@ -403,6 +1247,7 @@ mod tests {
                 anonymize_client_ip: false,
                 allowed_clients: vec![],
                 disallowed_clients: vec![],
+                extra: Default::default(),
             },
             safe_search: SafeSearchConfig {
                 enabled: true,
@@ -412,11 +1257,13 @@ mod tests {
                 pixabay: true,
                 yandex: true,
                 youtube: true,
+                extra: Default::default(),
             },
             safe_browsing: true,
             parental_control: ParentalControlConfig {
                 enabled: true,
                 sensitivity: None,
+                extra: Default::default(),
             },
             dhcp: DhcpStatus {
                 enabled: false,
@@ -425,12 +1272,14 @@ mod tests {
                 v6: None,
                 leases: Vec::new(),
                 static_leases: Vec::new(),
+                extra: Default::default(),
             },
             tls: TlsConfig::default(),
             profile_info: ProfileInfo {
                 name: "admin".to_string(),
                 language: "en".to_string(),
                 theme: "dark".to_string(),
+                extra: Default::default(),
             },
         };
 
@@ -606,6 +1455,7 @@ mod tests {
                 filters: vec![],
                 whitelist_filters: vec![],
                 user_rules: vec!["rule1".to_string()],
+                extra: Default::default(),
             },
             clients: vec![],
             dns: DnsConfig {
@@ -623,6 +1473,7 @@ mod tests {
                 upstream_mode: "".to_string(),
                 use_private_ptr_resolvers: false,
                 local_ptr_upstreams: vec![],
+                ..Default::default()
             },
             blocked_services: vec!["youtube".to_string()],
             rewrites: vec![],
@@ -630,6 +1481,7 @@ mod tests {
                 allowed_clients: vec![],
                 disallowed_clients: vec![],
                 blocked_hosts: vec![],
+                extra: Default::default(),
             },
             query_log_config: QueryLogConfig {
                 enabled: true,
@@ -637,6 +1489,7 @@ mod tests {
                 anonymize_client_ip: false,
                 allowed_clients: vec![],
                 disallowed_clients: vec![],
+                extra: Default::default(),
             },
             safe_search: SafeSearchConfig {
                 enabled: true,
@@ -646,11 +1499,13 @@ mod tests {
                 pixabay: true,
                 yandex: true,
                 youtube: true,
+                extra: Default::default(),
             },
             safe_browsing: true,
             parental_control: ParentalControlConfig {
                 enabled: true,
                 sensitivity: None,
+                extra: Default::default(),
             },
             dhcp: DhcpStatus {
                 enabled: false,
@@ -659,12 +1514,14 @@ mod tests {
                 v6: None,
                 leases: Vec::new(),
                 static_leases: Vec::new(),
+                extra: Default::default(),
             },
             tls: TlsConfig::default(),
             profile_info: ProfileInfo {
                 name: "admin".to_string(),
                 language: "en".to_string(),
                 theme: "dark".to_string(),
+                extra: Default::default(),
             },
         };
 
@@ -717,6 +1574,7 @@ mod tests {
                 filters: vec![],
                 whitelist_filters: vec![],
                 user_rules: vec![],
+                extra: Default::default(),
             },
             clients: vec![],
             dns: DnsConfig::default(),
@@ -729,6 +1587,7 @@ mod tests {
                 anonymize_client_ip: false,
                 allowed_clients: vec![],
                 disallowed_clients: vec![],
+                extra: Default::default(),
             },
             safe_search: SafeSearchConfig {
                 enabled: true,
@@ -738,11 +1597,13 @@ mod tests {
                 pixabay: true,
                 yandex: true,
                 youtube: true,
+                extra: Default::default(),
             },
             safe_browsing: true,
             parental_control: ParentalControlConfig {
                 enabled: true,
                 sensitivity: None,
+                extra: Default::default(),
             },
             dhcp: DhcpStatus {
                 enabled: false,
@@ -751,12 +1612,14 @@ mod tests {
                 v6: None,
                 leases: Vec::new(),
                 static_leases: Vec::new(),
+                extra: Default::default(),
             },
             tls: TlsConfig::default(),
             profile_info: ProfileInfo {
                 name: "admin".to_string(),
                 language: "en".to_string(),
                 theme: "dark".to_string(),
+                extra: Default::default(),
             },
         };
 
@@ -788,4 +1651,879 @@ mod tests {
         );
         assert!(!result.errors.is_empty());
     }
+
+    #[test]
+    fn test_sync_plan() {
+        use crate::adguard::models::*;
+        let filter = |url: &str, enabled: bool| Filter {
+            url: url.to_string(),
+            name: url.to_string(),
+            id: 1,
+            enabled,
+            last_updated: None,
+            rules_count: 0,
+            extra: Default::default(),
+        };
+        let master = SyncState {
+            filtering: FilteringConfig {
+                user_rules: vec!["||ads.example^".into(), "||shared^".into()],
+                filters: vec![filter("https://a", true), filter("https://b", false)],
+                interval: 24,
+                ..Default::default()
+            },
+            blocked_services: vec!["tiktok".into()],
+            rewrites: vec![DnsRewrite {
+                domain: "nas.lan".into(),
+                answer: "192.168.1.10".into(),
+                extra: Default::default(),
+            }],
+            dns: DnsConfig {
+                upstream_dns: vec!["9.9.9.9".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let replica = SyncState {
+            filtering: FilteringConfig {
+                user_rules: vec!["||shared^".into(), "||local^".into()],
+                filters: vec![filter("https://b", true), filter("https://c", true)],
+                interval: 24,
+                ..Default::default()
+            },
+            blocked_services: vec!["tiktok".into()],
+            rewrites: vec![DnsRewrite {
+                domain: "old.lan".into(),
+                answer: "10.0.0.1".into(),
+                extra: Default::default(),
+            }],
+            dns: DnsConfig {
+                upstream_dns: vec!["1.1.1.1".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let plan = master.plan(&replica, "full-overwrite", &ModuleSelection::default());
+        let module = |name: &str| plan.modules.iter().find(|m| m.module == name).unwrap();
+        assert_eq!(module("User Rules").add, vec!["||ads.example^"]);
+        assert_eq!(module("User Rules").remove, vec!["||local^"]);
+        assert_eq!(module("Filter Lists").add, vec!["https://a (https://a)"]);
+        assert_eq!(module("Filter Lists").remove, vec!["https://c (https://c)"]);
+        assert_eq!(
+            module("Filter Lists").changes,
+            vec![FieldChange {
+                field: "https://b.enabled".into(),
+                old: json!(true),
+                new: json!(false),
+            }]
+        );
+        assert_eq!(module("DNS Rewrites").add, vec!["nas.lan -> 192.168.1.10"]);
+        assert_eq!(module("DNS Rewrites").remove, vec!["old.lan -> 10.0.0.1"]);
+        assert_eq!(
+            module("DNS Config").changes,
+            vec![FieldChange {
+                field: "upstream_dns".into(),
+                old: json!(["1.1.1.1"]),
+                new: json!(["9.9.9.9"]),
+            }]
+        );
+        assert!(
+            plan.unchanged_modules
+                .contains(&"Blocked Services".to_string())
+        );
+        assert!(
+            plan.unchanged_modules
+                .contains(&"Filter Update Interval".to_string())
+        );
+        assert_eq!(
+            plan.skipped_modules,
+            vec!["DHCP Config", "TLS Config", "Profile Info"]
+        );
+
+        let text = plan.to_string();
+        assert!(text.contains("  - ||local^"));
+        assert!(text.contains("  ~ upstream_dns: [\"1.1.1.1\"] -> [\"9.9.9.9\"]"));
+
+        // Additive merge never removes anything
+        let plan = master.plan(&replica, "additive-merge", &ModuleSelection::default());
+        assert!(plan.modules.iter().all(|m| m.remove.is_empty()));
+        assert!(
+            master
+                .plan(&master, "full-overwrite", &ModuleSelection::default())
+                .is_empty()
+        );
+    }
+
+    /// Mounts read endpoints describing an empty instance, plus accepting writes.
+    async fn mount_instance(server: &wiremock::MockServer) {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let reads = [
+            (
+                "/control/filtering/status",
+                json!({"enabled": true, "interval": 24, "filters": [], "whitelist_filters": [], "user_rules": []}),
+            ),
+            ("/control/clients", json!({"clients": []})),
+            (
+                "/control/dns_info",
+                json!({
+                    "upstream_dns": [], "upstream_dns_file": "", "bootstrap_dns": [], "fallback_dns": [],
+                    "all_servers": false, "fastest_addr": false, "fastest_timeout": 0, "cache_size": 0,
+                    "cache_ttl_min": 0, "cache_ttl_max": 0, "cache_optimistic": false, "upstream_mode": "",
+                    "use_private_ptr_resolvers": false, "local_ptr_upstreams": []
+                }),
+            ),
+            ("/control/blocked_services/list", json!([])),
+            ("/control/rewrite/list", json!([])),
+            (
+                "/control/access/list",
+                json!({"allowed_clients": [], "disallowed_clients": [], "blocked_hosts": []}),
+            ),
+            (
+                "/control/querylog/config",
+                json!({"enabled": true, "interval": 1, "anonymize_client_ip": false, "allowed_clients": [], "disallowed_clients": []}),
+            ),
+            (
+                "/control/safesearch/status",
+                json!({"enabled": false, "bing": false, "duckduckgo": false, "google": false, "pixabay": false, "yandex": false, "youtube": false}),
+            ),
+            (
+                "/control/status",
+                json!({"version": "v", "language": "en", "protection_enabled": true}),
+            ),
+            ("/control/parental/status", json!({"enabled": false})),
+            (
+                "/control/dhcp/status",
+                json!({"enabled": false, "interface_name": "", "leases": [], "static_leases": []}),
+            ),
+            (
+                "/control/tls/status",
+                json!({
+                    "enabled": false, "server_name": "", "force_https": false, "port_https": 0, "port_dns_over_tls": 0, "port_dns_over_quic": 0,
+                    "certificate_chain": "", "private_key": "", "certificate_path": "", "private_key_path": "", "valid_cert": false, "valid_key": false, "valid_pair": false
+                }),
+            ),
+            (
+                "/control/profile",
+                json!({"name": "admin", "language": "en", "theme": "dark"}),
+            ),
+        ];
+        for (route, body) in reads {
+            Mock::given(method("GET"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(server)
+                .await;
+        }
+        for verb in ["POST", "PUT"] {
+            Mock::given(method(verb))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(server)
+                .await;
+        }
+    }
+
+    async fn posts(server: &wiremock::MockServer, route: &str) -> usize {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| r.method.as_str() == "POST" && r.url.path() == route)
+            .count()
+    }
+
+    async fn wait_for_posts(server: &wiremock::MockServer, route: &str, expected: usize) -> bool {
+        for _ in 0..50 {
+            if posts(server, route).await >= expected {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_fetch_full_legacy_query_log_config() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/control/status"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                json!({"version": "v0.107.20", "language": "en", "protection_enabled": true}),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/control/querylog_info"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                json!({"enabled": true, "interval": 90, "anonymize_client_ip": true}),
+            ))
+            .mount(&server)
+            .await;
+        mount_instance(&server).await;
+        let client = AdGuardClient::new(crate::config::InstanceConfig {
+            url: server.uri(),
+            ..Default::default()
+        });
+
+        let state = SyncState::fetch_full(&client, Some("Legacy".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(state.metadata.unwrap().version, "v0.107.20");
+        assert_eq!(state.query_log_config.interval, 90 * 24 * 60 * 60 * 1000);
+        assert!(state.query_log_config.anonymize_client_ip);
+    }
+
+    #[tokio::test]
+    async fn test_background_sync_slow_replica_does_not_block_others() {
+        use crate::config::InstanceConfig;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let master = MockServer::start().await;
+        mount_instance(&master).await;
+        let healthy = MockServer::start().await;
+        mount_instance(&healthy).await;
+        let slow = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(60)))
+            .mount(&slow)
+            .await;
+
+        let instance = |name: &str, url: String| InstanceConfig {
+            name: Some(name.to_string()),
+            url,
+            ..Default::default()
+        };
+        let mut config = AppConfig {
+            instances: vec![
+                instance("master", master.uri()),
+                instance("slow", slow.uri()),
+                instance("healthy", healthy.uri()),
+            ],
+            replicas: vec![
+                ReplicaConfig {
+                    instance: Some("slow".to_string()),
+                    ..Default::default()
+                },
+                ReplicaConfig {
+                    instance: Some("healthy".to_string()),
+                    mode: Some("full-overwrite".to_string()),
+                    ..Default::default()
+                },
+            ],
+            sync_concurrency: 2,
+            ..Default::default()
+        };
+        config.validate().unwrap();
+
+        let task = tokio::spawn(SyncState::run_background_sync(config));
+        let synced = wait_for_posts(&healthy, "/control/filtering/set_rules", 1).await;
+        task.abort();
+        assert!(
+            synced,
+            "healthy replica was not synced while the slow one hung"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_background_transactional_sync_outlasting_interval_completes() {
+        use crate::config::InstanceConfig;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let master = MockServer::start().await;
+        mount_instance(&master).await;
+        let replica = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/control/filtering/set_rules"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(1500)))
+            .with_priority(1)
+            .mount(&replica)
+            .await;
+        mount_instance(&replica).await;
+
+        let instance = |name: &str, url: String| InstanceConfig {
+            name: Some(name.to_string()),
+            url,
+            ..Default::default()
+        };
+        let mut config = AppConfig {
+            instances: vec![
+                instance("master", master.uri()),
+                instance("replica", replica.uri()),
+            ],
+            replicas: vec![ReplicaConfig {
+                instance: Some("replica".to_string()),
+                mode: Some("full-overwrite".to_string()),
+                sync_interval_seconds: Some(1),
+                transactional: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        config.validate().unwrap();
+        let history = config.sync_history.clone();
+
+        let task = tokio::spawn(SyncState::run_background_sync(config));
+        let mut records = Vec::new();
+        for _ in 0..50 {
+            records = history.get("replica").await;
+            if !records.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        task.abort();
+
+        let first = records.first().expect("no sync was recorded");
+        assert!(first.result.success, "{:?}", first.result.errors);
+        assert!(first.duration_ms >= 1500);
+        assert!(first.result.rollback.is_none());
+    }
+
+    #[test]
+    fn test_fingerprints() {
+        use crate::adguard::models::*;
+        let mut state = SyncState::default();
+        state.filtering.filters.push(Filter {
+            url: "https://a".into(),
+            name: "A".into(),
+            id: 1,
+            enabled: true,
+            last_updated: None,
+            rules_count: 10,
+            extra: Default::default(),
+        });
+        let before = state.fingerprints();
+        assert_eq!(before.len(), SYNC_MODULES.len());
+        assert_eq!(before, state.clone().fingerprints());
+
+        // Refresh results and leases are not configuration
+        state.filtering.filters[0].rules_count = 20;
+        state.filtering.filters[0].last_updated = Some("2026-01-01T00:00:00Z".into());
+        state.dhcp.leases.push(DhcpLease {
+            mac: "aa:bb:cc:dd:ee:ff".into(),
+            ip: "192.168.1.50".into(),
+            hostname: "phone".into(),
+            expires: None,
+        });
+        assert_eq!(before, state.fingerprints());
+
+        state.filtering.user_rules.push("||ads.example^".into());
+        state.filtering.filters[0].enabled = false;
+        let after = state.fingerprints();
+        let changed: Vec<_> = after
+            .iter()
+            .filter(|(module, hash)| before.get(*module) != Some(hash))
+            .map(|(module, _)| module.as_str())
+            .collect();
+        assert_eq!(changed, vec!["filter_lists", "user_rules"]);
+
+        // Saved fingerprints must match after a toolchain upgrade
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(before["user_rules"], fnv1a(b"[]"));
+    }
+
+    #[tokio::test]
+    async fn test_background_sync_pushes_master_changes() {
+        use crate::config::InstanceConfig;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let master = MockServer::start().await;
+        mount_instance(&master).await;
+        let replica = MockServer::start().await;
+        mount_instance(&replica).await;
+
+        let mut config = AppConfig {
+            instances: vec![
+                InstanceConfig {
+                    name: Some("master".to_string()),
+                    url: master.uri(),
+                    ..Default::default()
+                },
+                InstanceConfig {
+                    name: Some("replica".to_string()),
+                    url: replica.uri(),
+                    ..Default::default()
+                },
+            ],
+            replicas: vec![ReplicaConfig {
+                instance: Some("replica".to_string()),
+                ..Default::default()
+            }],
+            sync_poll_seconds: 1,
+            ..Default::default()
+        };
+        config.validate().unwrap();
+
+        let task = tokio::spawn(SyncState::run_background_sync(config));
+        // Startup pushes everything once
+        assert!(wait_for_posts(&replica, "/control/filtering/set_rules", 1).await);
+        assert_eq!(posts(&replica, "/control/dns_config").await, 1);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        Mock::given(method("GET"))
+            .and(path("/control/filtering/status"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "enabled": true, "interval": 24, "filters": [], "whitelist_filters": [],
+                "user_rules": ["||ads.example^"]
+            })))
+            .with_priority(1)
+            .mount(&master)
+            .await;
+
+        let pushed = wait_for_posts(&replica, "/control/filtering/set_rules", 2).await;
+        task.abort();
+        assert!(pushed, "master change was not pushed");
+        // Only the changed module was written again
+        assert_eq!(posts(&replica, "/control/dns_config").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_push_transactional_rolls_back_on_critical_failure() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let replica = MockServer::start().await;
+        mount_instance(&replica).await;
+        Mock::given(method("POST"))
+            .and(path("/control/access/set"))
+            .respond_with(ResponseTemplate::new(500))
+            .with_priority(1)
+            .mount(&replica)
+            .await;
+        let client = AdGuardClient::new(crate::config::InstanceConfig {
+            url: replica.uri(),
+            ..Default::default()
+        });
+        let mut master = SyncState::fetch(&client).await.unwrap();
+        master.filtering.user_rules = vec!["||ads.example^".to_string()];
+        let selection = ModuleSelection::default();
+
+        // A failing module outside the critical list is reported but kept
+        let critical = ModuleSelection::new(vec!["rewrites".into()], Some(vec![])).unwrap();
+        let result = master
+            .push_transactional(&client, "full-overwrite", &selection, &critical)
+            .await
+            .unwrap();
+        assert_eq!(result.failed_modules, vec!["Access List"]);
+        assert!(result.rollback.is_none());
+        assert_eq!(posts(&replica, "/control/dns_config").await, 1);
+
+        let critical = ModuleSelection::new(vec![], Some(vec![])).unwrap();
+        let result = master
+            .push_transactional(&client, "full-overwrite", &selection, &critical)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        let rollback = result.rollback.expect("replica was not rolled back");
+        assert!(rollback.applied_modules.contains(&"DNS Config".to_string()));
+        assert_eq!(rollback.failed_modules, vec!["Access List"]);
+        assert!(rollback.skipped_modules.contains(&"TLS Config".to_string()));
+        // The push and the restore both rewrote DNS settings
+        assert_eq!(posts(&replica, "/control/dns_config").await, 3);
+        let restored = replica
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .rfind(|r| r.url.path() == "/control/filtering/set_rules")
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&restored.body).contains("ads.example"));
+
+        // Nothing is pushed when the snapshot cannot be taken
+        let unreachable = AdGuardClient::new(crate::config::InstanceConfig {
+            url: "http://127.0.0.1:1".to_string(),
+            ..Default::default()
+        });
+        let err = master
+            .push_transactional(&unreachable, "full-overwrite", &selection, &critical)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("nothing was pushed"));
+    }
+
+    #[tokio::test]
+    async fn test_drift_after_rollback() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let replica = MockServer::start().await;
+        mount_instance(&replica).await;
+        Mock::given(method("POST"))
+            .and(path("/control/access/set"))
+            .respond_with(ResponseTemplate::new(500))
+            .with_priority(1)
+            .mount(&replica)
+            .await;
+        let client = AdGuardClient::new(crate::config::InstanceConfig {
+            url: replica.uri(),
+            ..Default::default()
+        });
+        let mut master = SyncState::fetch(&client).await.unwrap();
+        master.filtering.user_rules = vec!["||ads.example^".to_string()];
+        let selection = ModuleSelection::default();
+        let critical = ModuleSelection::new(vec![], Some(vec![])).unwrap();
+
+        let history = SyncHistoryStore::default();
+        let result = history
+            .track(
+                "replica",
+                "manual",
+                "full-overwrite",
+                master.fingerprints(),
+                master.push_transactional(&client, "full-overwrite", &selection, &critical),
+            )
+            .await
+            .unwrap();
+        assert!(result.applied_modules.contains(&"User Rules".to_string()));
+        assert!(result.rollback.is_some());
+
+        // The rollback restored the old rules, so the master's are still pending
+        let current = SyncState::fetch(&client).await.unwrap();
+        let plan = master.plan(&current, "full-overwrite", &selection);
+        let drift = Drift::classify(&plan, &master.fingerprints(), &history.get("replica").await);
+        assert!(drift.changed_on_replica.is_empty());
+        assert!(
+            drift
+                .pending_from_master
+                .contains(&"User Rules".to_string())
+        );
+    }
+
+    fn record(applied: &[&str], success: bool, fingerprints: BTreeMap<String, u64>) -> SyncRecord {
+        SyncRecord {
+            timestamp: "2026-10-18T10:00:00+00:00".to_string(),
+            mode: "additive-merge".to_string(),
+            trigger: "schedule".to_string(),
+            duration_ms: 120,
+            result: SyncResult {
+                success,
+                applied_modules: applied.iter().map(|m| m.to_string()).collect(),
+                failed_modules: Vec::new(),
+                skipped_modules: Vec::new(),
+                errors: if success {
+                    Vec::new()
+                } else {
+                    vec!["DNS Config: connection refused".to_string()]
+                },
+                ..Default::default()
+            },
+            master_fingerprints: fingerprints,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_history_store() {
+        use history::format_status;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("sync_history.json");
+        let store = SyncHistoryStore::open(Some(path.clone()), 2);
+        for success in [true, false, true] {
+            store
+                .record("garage", record(&["User Rules"], success, BTreeMap::new()))
+                .await;
+        }
+        assert_eq!(store.get("garage").await.len(), 2);
+        assert!(store.get("office").await.is_empty());
+
+        // Survives a restart
+        let reopened = SyncHistoryStore::open(Some(path), 2);
+        let records = reopened.get("garage").await;
+        assert_eq!(records.len(), 2);
+        assert_eq!(reopened.replicas().await, vec!["garage"]);
+
+        let status = format_status("garage", &records);
+        assert!(status.contains("garage: last sync 2026-10-18T10:00:00+00:00 (schedule, additive-merge, 120 ms) succeeded, 1 module(s) applied."));
+        assert!(status.contains("1 of the last 2 sync(s) failed."));
+        assert_eq!(format_status("office", &[]), "office: never synced.");
+    }
+
+    #[test]
+    fn test_drift_classification() {
+        let mut master = SyncState::default();
+        master.filtering.user_rules.push("||ads.example^".into());
+        master.dns.upstream_dns.push("9.9.9.9".into());
+        let synced = master.fingerprints();
+        let records = vec![record(&["User Rules", "DNS Config"], true, synced)];
+
+        // Someone edits DNS on the replica; the master adds a rewrite afterwards
+        let mut replica = master.clone();
+        replica.dns.upstream_dns = vec!["1.1.1.1".into()];
+        master.rewrites.push(crate::adguard::models::DnsRewrite {
+            domain: "nas.lan".into(),
+            answer: "192.168.1.10".into(),
+            extra: Default::default(),
+        });
+
+        let plan = master.plan(&replica, "additive-merge", &ModuleSelection::default());
+        let drift = Drift::classify(&plan, &master.fingerprints(), &records);
+        assert_eq!(drift.changed_on_replica, vec!["DNS Config"]);
+        assert_eq!(drift.pending_from_master, vec!["DNS Rewrites"]);
+
+        // A module whose restore failed is in an unknown state, not pushed
+        let mut rolled_back = records[0].clone();
+        rolled_back.result.rollback = Some(Box::new(SyncResult {
+            failed_modules: vec!["DNS Config".to_string()],
+            ..Default::default()
+        }));
+        let drift = Drift::classify(&plan, &master.fingerprints(), &[rolled_back]);
+        assert!(drift.changed_on_replica.is_empty());
+        assert!(
+            drift
+                .pending_from_master
+                .contains(&"DNS Config".to_string())
+        );
+    }
+
+    #[test]
+    fn test_jitter() {
+        assert_eq!(jitter(0), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter(5) < Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn test_module_selection() {
+        let default = ModuleSelection::default();
+        assert!(default.allows("rewrites"));
+        for module in DEFAULT_EXCLUDED_MODULES {
+            assert!(!default.allows(module));
+        }
+
+        let include = ModuleSelection::new(vec!["rewrites".into(), "dhcp".into()], None).unwrap();
+        assert!(include.allows("dhcp"));
+        assert!(include.allows("rewrites"));
+        assert!(!include.allows("clients"));
+
+        let exclude = ModuleSelection::new(vec![], Some(vec!["clients".into()])).unwrap();
+        assert!(exclude.allows("tls"));
+        assert!(!exclude.allows("clients"));
+
+        assert_eq!(
+            default.only(&["rewrites".into(), "dhcp".into()]),
+            Some(ModuleSelection::new(vec!["rewrites".into()], Some(vec![])).unwrap())
+        );
+        assert_eq!(default.only(&["tls".into()]), None);
+
+        assert!(ModuleSelection::new(vec!["firewall".into()], None).is_err());
+        assert!(ModuleSelection::new(vec![], Some(vec!["firewall".into()])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_sync_ignores_server_populated_fields() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let device = json!({
+            "name": "nas", "ids": ["192.168.1.10"], "use_global_settings": true,
+            "filtering_enabled": true, "parental_enabled": false,
+            "safebrowsing_enabled": false, "safesearch_enabled": false
+        });
+        let mut on_replica = device.clone();
+        on_replica["whois_info"] = json!({"country": "DE"});
+        on_replica["disallowed"] = json!(false);
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/control/clients"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({"clients": [on_replica]})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/control/clients/update"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let client = AdGuardClient::new(crate::config::InstanceConfig {
+            url: server.uri(),
+            ..Default::default()
+        });
+
+        let master = SyncState {
+            clients: vec![serde_json::from_value(device).unwrap()],
+            ..Default::default()
+        };
+        let replica = SyncState {
+            clients: vec![serde_json::from_value(on_replica).unwrap()],
+            ..Default::default()
+        };
+        let selection = ModuleSelection::new(vec!["clients".into()], Some(vec![])).unwrap();
+        assert!(
+            master
+                .plan(&replica, "full-overwrite", &selection)
+                .is_empty()
+        );
+
+        let result = master
+            .push_selected(&client, "full-overwrite", &selection)
+            .await
+            .unwrap();
+        assert_eq!(result.applied_modules, vec!["Clients"]);
+    }
+
+    #[tokio::test]
+    async fn test_push_clients_and_filter_lists() {
+        use crate::adguard::models::*;
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let client = AdGuardClient::new(crate::config::InstanceConfig {
+            url: server.uri(),
+            ..Default::default()
+        });
+
+        let filter = |url: &str, name: &str, enabled: bool| Filter {
+            url: url.to_string(),
+            name: name.to_string(),
+            id: 1,
+            enabled,
+            last_updated: None,
+            rules_count: 0,
+            extra: Default::default(),
+        };
+        let device = |name: &str, ip: &str| AdGuardClientDevice {
+            name: name.to_string(),
+            ids: vec![ip.to_string()],
+            ..Default::default()
+        };
+        let state = SyncState {
+            filtering: FilteringConfig {
+                enabled: true,
+                interval: 72,
+                filters: vec![
+                    filter("https://lists/new.txt", "New", false),
+                    filter("https://lists/kept.txt", "Renamed", true),
+                ],
+                whitelist_filters: vec![filter("https://lists/allow.txt", "Allow", true)],
+                user_rules: vec![],
+                extra: Default::default(),
+            },
+            clients: vec![device("nas", "192.168.1.10"), device("tv", "192.168.1.30")],
+            ..Default::default()
+        };
+
+        Mock::given(method("GET"))
+            .and(path("/control/filtering/status"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "enabled": false, "interval": 24, "user_rules": [],
+                "filters": [
+                    {"url": "https://lists/kept.txt", "name": "Kept", "id": 1, "enabled": true, "last_updated": null, "rules_count": 0},
+                    {"url": "https://lists/stale.txt", "name": "Stale", "id": 2, "enabled": true, "last_updated": null, "rules_count": 0}
+                ],
+                "whitelist_filters": []
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/control/clients"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "clients": [
+                    {"name": "nas", "ids": ["192.168.1.11"], "use_global_settings": true, "filtering_enabled": false,
+                     "parental_enabled": false, "safebrowsing_enabled": false, "safesearch_enabled": false},
+                    {"name": "old", "ids": ["192.168.1.99"], "use_global_settings": true, "filtering_enabled": false,
+                     "parental_enabled": false, "safebrowsing_enabled": false, "safesearch_enabled": false}
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let expect =
+            async |http_method: &str, endpoint: &str, body: serde_json::Value, times: u64| {
+                Mock::given(method(http_method))
+                    .and(path(endpoint))
+                    .and(body_partial_json(body))
+                    .respond_with(ResponseTemplate::new(200))
+                    .expect(times)
+                    .mount(&server)
+                    .await;
+            };
+        expect(
+            "POST",
+            "/control/filtering/add_url",
+            json!({"url": "https://lists/new.txt", "whitelist": false}),
+            2,
+        )
+        .await;
+        expect(
+            "POST",
+            "/control/filtering/add_url",
+            json!({"url": "https://lists/allow.txt", "whitelist": true}),
+            2,
+        )
+        .await;
+        expect(
+            "POST",
+            "/control/filtering/set_url",
+            json!({"url": "https://lists/new.txt", "data": {"enabled": false}}),
+            2,
+        )
+        .await;
+        expect(
+            "POST",
+            "/control/filtering/set_url",
+            json!({"url": "https://lists/kept.txt", "data": {"name": "Renamed"}}),
+            2,
+        )
+        .await;
+        expect(
+            "POST",
+            "/control/filtering/remove_url",
+            json!({"url": "https://lists/stale.txt"}),
+            1,
+        )
+        .await;
+        expect(
+            "POST",
+            "/control/filtering/config",
+            json!({"enabled": false, "interval": 72}),
+            2,
+        )
+        .await;
+        expect("POST", "/control/clients/add", json!({"name": "tv"}), 2).await;
+        expect(
+            "POST",
+            "/control/clients/update",
+            json!({"name": "nas", "data": {"ids": ["192.168.1.10"]}}),
+            2,
+        )
+        .await;
+        expect("POST", "/control/clients/delete", json!({"name": "old"}), 1).await;
+
+        let result = state
+            .push_to_replica(&client, "full-overwrite")
+            .await
+            .unwrap();
+        for module in [
+            "Filter Lists",
+            "Allowlists",
+            "Filter Update Interval",
+            "Clients",
+        ] {
+            assert!(
+                result.applied_modules.contains(&module.to_string()),
+                "{}",
+                module
+            );
+        }
+
+        // Additive merge leaves replica-only lists and clients in place
+        let result = state
+            .push_to_replica(&client, "additive-merge")
+            .await
+            .unwrap();
+        assert!(result.applied_modules.contains(&"Clients".to_string()));
+    }
 }
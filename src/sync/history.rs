@@ -0,0 +1,211 @@
+use super::{SyncPlan, SyncResult, module_key};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+/// Outcomes kept per replica unless `sync_history_size` says otherwise.
+pub const DEFAULT_HISTORY_SIZE: usize = 20;
+
+/// Outcome of one push to a replica.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncRecord {
+    pub timestamp: String,
+    pub mode: String,
+    /// `manual`, `schedule` or `change`.
+    pub trigger: String,
+    pub duration_ms: u64,
+    #[serde(flatten)]
+    pub result: SyncResult,
+    /// Master module fingerprints at push time, used to tell edits made on the
+    /// replica apart from master changes that were not synced yet.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub master_fingerprints: BTreeMap<String, u64>,
+}
+
+/// Last sync outcomes per replica, shared by background sync and `sync_instances`.
+/// With a path, every new record is written to disk.
+#[derive(Debug, Clone)]
+pub struct SyncHistoryStore {
+    path: Option<PathBuf>,
+    limit: usize,
+    records: Arc<Mutex<BTreeMap<String, VecDeque<SyncRecord>>>>,
+}
+
+impl Default for SyncHistoryStore {
+    fn default() -> Self {
+        Self::open(None, DEFAULT_HISTORY_SIZE)
+    }
+}
+
+impl SyncHistoryStore {
+    /// Loads the history saved at `path`, if any. An unreadable file is logged
+    /// and replaced by the next write.
+    pub fn open(path: Option<PathBuf>, limit: usize) -> Self {
+        let records = path
+            .as_deref()
+            .and_then(|path| match std::fs::read(path) {
+                Ok(bytes) => serde_json::from_slice(&bytes)
+                    .map_err(|e| tracing::warn!("Ignoring sync history {}: {}", path.display(), e))
+                    .ok(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    tracing::warn!("Failed to read sync history {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            limit,
+            records: Arc::new(Mutex::new(records)),
+        }
+    }
+
+    pub async fn record(&self, replica: &str, record: SyncRecord) {
+        let mut records = self.records.lock().await;
+        let entries = records.entry(replica.to_string()).or_default();
+        entries.push_back(record);
+        while entries.len() > self.limit {
+            entries.pop_front();
+        }
+        if let Some(path) = &self.path
+            && let Err(e) = persist(path, &records).await
+        {
+            tracing::warn!("Failed to save sync history {}: {}", path.display(), e);
+        }
+    }
+
+    /// Runs `push` and records its outcome and duration for `replica`.
+    pub async fn track(
+        &self,
+        replica: &str,
+        trigger: &str,
+        mode: &str,
+        master_fingerprints: BTreeMap<String, u64>,
+        push: impl Future<Output = anyhow::Result<SyncResult>>,
+    ) -> anyhow::Result<SyncResult> {
+        let timestamp = Utc::now().to_rfc3339();
+        let started = Instant::now();
+        let outcome = push.await;
+        let result = match &outcome {
+            Ok(result) => result.clone(),
+            Err(e) => SyncResult {
+                errors: vec![e.to_string()],
+                ..Default::default()
+            },
+        };
+        self.record(
+            replica,
+            SyncRecord {
+                timestamp,
+                mode: mode.to_string(),
+                trigger: trigger.to_string(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                result,
+                master_fingerprints,
+            },
+        )
+        .await;
+        outcome
+    }
+
+    /// Records for `replica`, oldest first.
+    pub async fn get(&self, replica: &str) -> Vec<SyncRecord> {
+        self.records
+            .lock()
+            .await
+            .get(replica)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn replicas(&self) -> Vec<String> {
+        self.records.lock().await.keys().cloned().collect()
+    }
+}
+
+async fn persist(
+    path: &Path,
+    records: &BTreeMap<String, VecDeque<SyncRecord>>,
+) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // Write then rename, so a crash never leaves a truncated file behind
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(records)?).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+/// Summary of a replica's recent syncs, one line for the last one.
+pub fn format_status(replica: &str, records: &[SyncRecord]) -> String {
+    let Some(last) = records.last() else {
+        return format!("{}: never synced.", replica);
+    };
+    let mut text = format!(
+        "{}: last sync {} ({}, {}, {} ms) ",
+        replica, last.timestamp, last.trigger, last.mode, last.duration_ms
+    );
+    if last.result.success {
+        text.push_str(&format!(
+            "succeeded, {} module(s) applied.",
+            last.result.applied_modules.len()
+        ));
+    } else {
+        text.push_str(&format!("failed: {}.", last.result.errors.join("; ")));
+        if let Some(rollback) = &last.result.rollback {
+            text.push_str(if rollback.success {
+                " Rolled back."
+            } else {
+                " Rollback failed."
+            });
+        }
+    }
+    let failures = records.iter().filter(|r| !r.result.success).count();
+    text.push_str(&format!(
+        " {} of the last {} sync(s) failed.",
+        failures,
+        records.len()
+    ));
+    text
+}
+
+/// Modules where a replica differs from the master, split by cause.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct Drift {
+    /// Pushed earlier and unchanged on the master since: edited on the replica.
+    pub changed_on_replica: Vec<String>,
+    /// Changed on the master, or never pushed, since the last sync.
+    pub pending_from_master: Vec<String>,
+}
+
+impl Drift {
+    /// Sorts each module `plan` would change, using the fingerprint the master
+    /// had when that module was last pushed successfully and left alone by any rollback.
+    pub fn classify(
+        plan: &SyncPlan,
+        master_fingerprints: &BTreeMap<String, u64>,
+        records: &[SyncRecord],
+    ) -> Self {
+        let mut drift = Self::default();
+        for module in &plan.modules {
+            let name = &module.module;
+            let key = module_key(name).unwrap_or_default();
+            let pushed = records
+                .iter()
+                .rev()
+                .find(|r| r.result.applied_modules.contains(name) && !r.result.rolled_back(name))
+                .and_then(|r| r.master_fingerprints.get(key));
+            if pushed.is_some() && pushed == master_fingerprints.get(key) {
+                drift.changed_on_replica.push(name.clone());
+            } else {
+                drift.pending_from_master.push(name.clone());
+            }
+        }
+        drift
+    }
+}
@@ -1,5 +1,14 @@
-use super::ToolRegistry;
-use crate::adguard::{AdGuardClientDevice, StaticLease};
+use super::{ToolRegistry, invalid_params, string_list};
+use crate::adguard::oui;
+use crate::adguard::{
+    AdGuardClient, AdGuardClientDevice, DhcpConfig, DhcpStatus, SafeSearchConfig, StaticLease,
+    WeeklySchedule,
+};
+use serde_json::Value;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Query log entries scanned for devices unless `log_limit` is given.
+const DEFAULT_DISCOVERY_LOG_LIMIT: u64 = 1000;
 
 pub fn register(registry: &mut ToolRegistry) {
     registry.register(
@@ -15,7 +24,9 @@ pub fn register(registry: &mut ToolRegistry) {
                         "list_clients", "get_client_info", "add_client", "update_client",
                         "delete_client", "get_activity_report", "get_access_list",
                         "update_access_list", "list_dhcp_leases", "add_static_lease",
-                        "remove_static_lease"
+                        "remove_static_lease", "discover_clients", "promote_client",
+                        "update_static_lease", "list_dhcp_interfaces", "find_active_dhcp",
+                        "get_dhcp_config", "set_dhcp_config", "reset_dhcp", "reset_dhcp_leases"
                     ]
                 },
                 "identifier": { "type": "string", "description": "IP, MAC, or Name" },
@@ -27,13 +38,46 @@ pub fn register(registry: &mut ToolRegistry) {
                 "parental_enabled": { "type": "boolean" },
                 "safebrowsing_enabled": { "type": "boolean" },
                 "safesearch_enabled": { "type": "boolean" },
+                "safe_search": {
+                    "type": "object",
+                    "description": "Per-client safe search engines",
+                    "properties": {
+                        "enabled": { "type": "boolean" }, "bing": { "type": "boolean" },
+                        "duckduckgo": { "type": "boolean" }, "ecosia": { "type": "boolean" },
+                        "google": { "type": "boolean" }, "pixabay": { "type": "boolean" },
+                        "yandex": { "type": "boolean" }, "youtube": { "type": "boolean" }
+                    }
+                },
+                "use_global_blocked_services": { "type": "boolean" },
+                "blocked_services": { "type": "array", "items": { "type": "string" }, "description": "Service IDs blocked for this client" },
+                "blocked_services_schedule": {
+                    "type": "object",
+                    "description": "Weekly times when this client's service blocking is paused, e.g. {\"time_zone\": \"Europe/Berlin\", \"mon\": {\"start\": \"19:00\", \"end\": \"23:59\"}}"
+                },
+                "upstreams": { "type": "array", "items": { "type": "string" }, "description": "Client-specific upstream DNS servers" },
+                "upstreams_cache_enabled": { "type": "boolean" },
+                "upstreams_cache_size": { "type": "integer", "minimum": 0 },
+                "tags": { "type": "array", "items": { "type": "string" }, "description": "Tags from AdGuard's supported list, e.g. device_tablet, user_child" },
+                "ignore_querylog": { "type": "boolean" },
+                "ignore_statistics": { "type": "boolean" },
                 "limit": { "type": "integer", "minimum": 1, "maximum": 100 },
+                "log_limit": { "type": "integer", "minimum": 0, "description": "Query log entries scanned by discover_clients and promote_client (default 1000)" },
+                "unmanaged_only": { "type": "boolean", "description": "discover_clients: hide devices already covered by a persistent client" },
                 "allowed_clients": { "type": "array", "items": { "type": "string" } },
                 "disallowed_clients": { "type": "array", "items": { "type": "string" } },
                 "blocked_hosts": { "type": "array", "items": { "type": "string" } },
                 "mac": { "type": "string" },
                 "ip": { "type": "string" },
-                "hostname": { "type": "string" }
+                "hostname": { "type": "string" },
+                "interface_name": { "type": "string", "description": "Network interface for DHCP" },
+                "dhcp_enabled": { "type": "boolean" },
+                "gateway_ip": { "type": "string" },
+                "subnet_mask": { "type": "string" },
+                "range_start": { "type": "string", "description": "First IPv4 address handed out" },
+                "range_end": { "type": "string", "description": "Last IPv4 address handed out" },
+                "lease_duration": { "type": "integer", "minimum": 0, "description": "IPv4 lease duration in seconds" },
+                "v6_range_start": { "type": "string", "description": "First IPv6 address handed out" },
+                "v6_lease_duration": { "type": "integer", "minimum": 0, "description": "IPv6 lease duration in seconds" }
             },
             "required": ["action"]
         }),
@@ -46,7 +90,16 @@ pub fn register(registry: &mut ToolRegistry) {
                 match action.as_str() {
                     "list_clients" => {
                         let res = client.list_clients().await?;
-                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&res)? }] }))
+                        let mut annotated = Vec::new();
+                        for device in &res {
+                            let mut value = serde_json::to_value(device)?;
+                            let vendors: serde_json::Map<String, Value> = device.ids.iter()
+                                .filter_map(|id| oui::lookup(id).map(|info| (id.clone(), Value::String(info.label()))))
+                                .collect();
+                            if !vendors.is_empty() { value["mac_vendors"] = Value::Object(vendors); }
+                            annotated.push(value);
+                        }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&annotated)? }] }))
                     }
                     "get_client_info" => {
                         let id = params["identifier"].as_str().unwrap_or_default();
@@ -62,7 +115,9 @@ pub fn register(registry: &mut ToolRegistry) {
                             parental_enabled: params["parental_enabled"].as_bool().unwrap_or(false),
                             safebrowsing_enabled: params["safebrowsing_enabled"].as_bool().unwrap_or(true),
                             safesearch_enabled: params["safesearch_enabled"].as_bool().unwrap_or(false),
+                            ..Default::default()
                         };
+                        let device = apply_client_settings(&client, device, &params).await?;
                         client.add_client(device).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Client added" }] }))
                     }
@@ -77,7 +132,9 @@ pub fn register(registry: &mut ToolRegistry) {
                             parental_enabled: params["parental_enabled"].as_bool().unwrap_or(curr.parental_enabled),
                             safebrowsing_enabled: params["safebrowsing_enabled"].as_bool().unwrap_or(curr.safebrowsing_enabled),
                             safesearch_enabled: params["safesearch_enabled"].as_bool().unwrap_or(curr.safesearch_enabled),
+                            ..curr
                         };
+                        let device = apply_client_settings(&client, device, &params).await?;
                         client.update_client(old.to_string(), device).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Client updated" }] }))
                     }
@@ -98,7 +155,7 @@ pub fn register(registry: &mut ToolRegistry) {
                             *domains.entry(entry.question.name.clone()).or_insert(0) += 1;
                         }
                         let mut top: Vec<_> = domains.into_iter().collect();
-                        top.sort_by(|a, b| b.1.cmp(&a.1)); top.truncate(5);
+                        top.sort_by_key(|b| std::cmp::Reverse(b.1)); top.truncate(5);
                         let mut text = format!("Report for {}: Analyzed={}, Blocked={}\nTop Domains:\n", id, total, blocked);
                         for (d, c) in top { text.push_str(&format!("- {}: {}\n", d, c)); }
                         if total == 0 { text = format!("No activity for {}", id); }
@@ -116,12 +173,67 @@ pub fn register(registry: &mut ToolRegistry) {
                         client.set_access_list(list).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Access list updated" }] }))
                     }
+                    "discover_clients" => {
+                        let log_limit = params["log_limit"].as_u64().unwrap_or(DEFAULT_DISCOVERY_LOG_LIMIT) as usize;
+                        let unmanaged_only = params["unmanaged_only"].as_bool().unwrap_or(false);
+                        let devices = client.discover_clients(log_limit).await?;
+                        let mut text = String::new();
+                        for d in devices.iter().filter(|d| !unmanaged_only || d.persistent_client.is_none()) {
+                            text.push_str(&format!("- {}", d.ips.join(", ")));
+                            if !d.macs.is_empty() { text.push_str(&format!(" [{}]", d.macs.join(", "))); }
+                            if let Some(vendor) = &d.vendor { text.push_str(&format!(" {{{}}}", vendor)); }
+                            else if d.randomized_mac { text.push_str(" {randomized MAC}"); }
+                            if !d.names.is_empty() { text.push_str(&format!(" {}", d.names.join(" / "))); }
+                            text.push_str(&format!("\n  Sources: {}; Queries: {}", d.sources.join(", "), d.queries));
+                            if let Some(seen) = &d.last_seen { text.push_str(&format!("; Last seen: {}", seen)); }
+                            match &d.persistent_client {
+                                Some(name) => text.push_str(&format!("\n  Persistent client: {}\n", name)),
+                                None => text.push_str("\n  Not a persistent client\n"),
+                            }
+                        }
+                        if text.is_empty() { text = "No devices discovered".to_string(); }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "promote_client" => {
+                        let id = params["identifier"].as_str().unwrap_or_default();
+                        let log_limit = params["log_limit"].as_u64().unwrap_or(DEFAULT_DISCOVERY_LOG_LIMIT) as usize;
+                        let devices = client.discover_clients(log_limit).await?;
+                        let Some(found) = devices.into_iter().find(|d| d.matches(id)) else {
+                            return Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("No discovered device matches {}", id) }], "isError": true }));
+                        };
+                        if let Some(existing) = &found.persistent_client {
+                            return Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("{} is already the persistent client '{}'", id, existing) }], "isError": true }));
+                        }
+                        // MACs survive DHCP address changes, so prefer them as identifiers
+                        let ids = if found.macs.is_empty() { found.ips.clone() } else { found.macs.clone() };
+                        let name = params["name"].as_str().map(|s| s.to_string())
+                            .or_else(|| found.names.first().cloned())
+                            .or_else(|| found.vendor.as_ref().zip(found.macs.first()).map(|(vendor, mac)| {
+                                let suffix: String = mac.chars().filter(|c| c.is_ascii_hexdigit()).skip(6).collect();
+                                format!("{} {}", vendor, suffix)
+                            }))
+                            .unwrap_or_else(|| id.to_string());
+                        let device = AdGuardClientDevice {
+                            name: name.clone(),
+                            ids: ids.clone(),
+                            use_global_settings: params["use_global_settings"].as_bool().unwrap_or(true),
+                            filtering_enabled: params["filtering_enabled"].as_bool().unwrap_or(true),
+                            parental_enabled: params["parental_enabled"].as_bool().unwrap_or(false),
+                            safebrowsing_enabled: params["safebrowsing_enabled"].as_bool().unwrap_or(true),
+                            safesearch_enabled: params["safesearch_enabled"].as_bool().unwrap_or(false),
+                            use_global_blocked_services: Some(true),
+                            ..Default::default()
+                        };
+                        let device = apply_client_settings(&client, device, &params).await?;
+                        client.add_client(device).await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("Promoted {} to persistent client '{}' (ids: {})", id, name, ids.join(", ")) }] }))
+                    }
                     "list_dhcp_leases" => {
                         let res = client.get_dhcp_status().await?;
                         let mut text = format!("DHCP Enabled: {}, Interface: {}\nDynamic:\n", res.enabled, res.interface_name);
-                        for l in res.leases { text.push_str(&format!("- {} ({}) expires {}\n", l.hostname, l.ip, l.expires.as_deref().unwrap_or("Never"))); }
+                        for l in res.leases { text.push_str(&format!("- {} ({}) [{}] expires {}\n", l.hostname, l.ip, oui::annotate(&l.mac), l.expires.as_deref().unwrap_or("Never"))); }
                         text.push_str("Static:\n");
-                        for l in res.static_leases { text.push_str(&format!("- {} ({}) [{}]\n", l.hostname, l.ip, l.mac)); }
+                        for l in res.static_leases { text.push_str(&format!("- {} ({}) [{}]\n", l.hostname, l.ip, oui::annotate(&l.mac))); }
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
                     }
                     "add_static_lease" => {
@@ -130,6 +242,8 @@ pub fn register(registry: &mut ToolRegistry) {
                             ip: params["ip"].as_str().unwrap_or_default().to_string(),
                             hostname: params["hostname"].as_str().unwrap_or_default().to_string(),
                         };
+                        let status = client.get_dhcp_status().await?;
+                        validate_static_lease(&status, &lease, false).map_err(invalid_params)?;
                         client.add_static_lease(lease).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Static lease added" }] }))
                     }
@@ -142,6 +256,87 @@ pub fn register(registry: &mut ToolRegistry) {
                         client.remove_static_lease(lease).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Static lease removed" }] }))
                     }
+                    "update_static_lease" => {
+                        let lease = StaticLease {
+                            mac: params["mac"].as_str().unwrap_or_default().to_string(),
+                            ip: params["ip"].as_str().unwrap_or_default().to_string(),
+                            hostname: params["hostname"].as_str().unwrap_or_default().to_string(),
+                        };
+                        let status = client.get_dhcp_status().await?;
+                        validate_static_lease(&status, &lease, true).map_err(invalid_params)?;
+                        client.update_static_lease(lease).await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Static lease updated" }] }))
+                    }
+                    "list_dhcp_interfaces" => {
+                        let interfaces = client.list_dhcp_interfaces().await?;
+                        let mut text = String::new();
+                        for i in interfaces {
+                            text.push_str(&format!("- {} [{}] flags: {}\n", i.name, oui::annotate(&i.hardware_address), i.flags));
+                            if !i.gateway_ip.is_empty() { text.push_str(&format!("  Gateway: {}\n", i.gateway_ip)); }
+                            if !i.ipv4_addresses.is_empty() { text.push_str(&format!("  IPv4: {}\n", i.ipv4_addresses.join(", "))); }
+                            if !i.ipv6_addresses.is_empty() { text.push_str(&format!("  IPv6: {}\n", i.ipv6_addresses.join(", "))); }
+                        }
+                        if text.is_empty() { text = "No interfaces found".to_string(); }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "find_active_dhcp" => {
+                        let iface = match params["interface_name"].as_str() {
+                            Some(i) => i.to_string(),
+                            None => client.get_dhcp_status().await?.interface_name,
+                        };
+                        let res = client.find_active_dhcp(&iface).await?;
+                        let describe = |found: &str, error: &str| match found {
+                            "yes" => "another DHCP server is active".to_string(),
+                            "no" => "no other DHCP server found".to_string(),
+                            _ => format!("check failed: {}", error),
+                        };
+                        let mut text = format!("DHCP check on {}:\n", iface);
+                        text.push_str(&format!("IPv4: {}\n", describe(&res.v4.other_server.found, &res.v4.other_server.error)));
+                        match res.v4.static_ip.is_static.as_str() {
+                            "yes" => text.push_str("Static IP: configured\n"),
+                            "no" => text.push_str(&format!("Static IP: not configured (current address {})\n", res.v4.static_ip.ip)),
+                            _ => {}
+                        }
+                        text.push_str(&format!("IPv6: {}\n", describe(&res.v6.other_server.found, &res.v6.other_server.error)));
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "get_dhcp_config" => {
+                        let status = client.get_dhcp_status().await?;
+                        let config = DhcpConfig::from(&status);
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&config)? }] }))
+                    }
+                    "set_dhcp_config" => {
+                        let mut status = client.get_dhcp_status().await?;
+                        if let Some(e) = params["dhcp_enabled"].as_bool() { status.enabled = e; }
+                        if let Some(i) = params["interface_name"].as_str() { status.interface_name = i.to_string(); }
+                        // Only touch an address family the caller passed settings for
+                        let given = |keys: &[&str]| keys.iter().any(|k| !params[*k].is_null());
+                        if given(&["gateway_ip", "subnet_mask", "range_start", "range_end", "lease_duration"]) {
+                            let v4 = status.v4.get_or_insert_with(Default::default);
+                            if let Some(g) = params["gateway_ip"].as_str() { v4.gateway_ip = Some(g.to_string()); }
+                            if let Some(m) = params["subnet_mask"].as_str() { v4.subnet_mask = Some(m.to_string()); }
+                            if let Some(r) = params["range_start"].as_str() { v4.range_start = Some(r.to_string()); }
+                            if let Some(r) = params["range_end"].as_str() { v4.range_end = Some(r.to_string()); }
+                            if let Some(d) = params["lease_duration"].as_u64() { v4.lease_duration = Some(d as u32); }
+                        }
+                        if given(&["v6_range_start", "v6_lease_duration"]) {
+                            let v6 = status.v6.get_or_insert_with(Default::default);
+                            if let Some(r) = params["v6_range_start"].as_str() { v6.range_start = Some(r.to_string()); }
+                            if let Some(d) = params["v6_lease_duration"].as_u64() { v6.lease_duration = Some(d as u32); }
+                        }
+
+                        validate_dhcp_config(&status).map_err(invalid_params)?;
+                        client.set_dhcp_config(status).await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": "DHCP config updated" }] }))
+                    }
+                    "reset_dhcp" => {
+                        client.reset_dhcp().await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": "DHCP configuration reset" }] }))
+                    }
+                    "reset_dhcp_leases" => {
+                        client.reset_dhcp_leases().await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": "DHCP leases reset" }] }))
+                    }
                     _ => Err(crate::error::Error::Mcp(crate::mcp::ResponseError {
                         code: -32602, message: format!("Unknown action: {}", action), data: None,
                     })),
@@ -150,3 +345,224 @@ pub fn register(registry: &mut ToolRegistry) {
         },
     );
 }
+
+async fn apply_client_settings(
+    client: &AdGuardClient,
+    mut device: AdGuardClientDevice,
+    params: &Value,
+) -> crate::error::Result<AdGuardClientDevice> {
+    if let Some(ids) = string_list(&params["blocked_services"]) {
+        device.blocked_services = Some(ids);
+        device.use_global_blocked_services = Some(false);
+    }
+    if let Some(g) = params["use_global_blocked_services"].as_bool() {
+        device.use_global_blocked_services = Some(g);
+    }
+    if !params["blocked_services_schedule"].is_null() {
+        let schedule = WeeklySchedule::from_params(&params["blocked_services_schedule"])
+            .map_err(|e| invalid_params(format!("Invalid blocked_services_schedule: {}", e)))?;
+        device.blocked_services_schedule = Some(schedule);
+    }
+    if let Some(upstreams) = string_list(&params["upstreams"]) {
+        device.upstreams = Some(upstreams);
+    }
+    if let Some(c) = params["upstreams_cache_enabled"].as_bool() {
+        device.upstreams_cache_enabled = Some(c);
+    }
+    if let Some(size) = params["upstreams_cache_size"].as_u64() {
+        device.upstreams_cache_size = Some(size as u32);
+    }
+    if let Some(tags) = string_list(&params["tags"]) {
+        if !tags.is_empty() {
+            let supported = client.list_supported_client_tags().await?;
+            if let Some(bad) = tags
+                .iter()
+                .find(|t| !supported.is_empty() && !supported.contains(t))
+            {
+                return Err(invalid_params(format!(
+                    "Unsupported tag: {} (supported: {})",
+                    bad,
+                    supported.join(", ")
+                )));
+            }
+        }
+        device.tags = Some(tags);
+    }
+    if let Some(i) = params["ignore_querylog"].as_bool() {
+        device.ignore_querylog = Some(i);
+    }
+    if let Some(i) = params["ignore_statistics"].as_bool() {
+        device.ignore_statistics = Some(i);
+    }
+
+    let engines = &params["safe_search"];
+    if engines.is_object() {
+        let mut safe_search = device.safe_search.take().unwrap_or(SafeSearchConfig {
+            enabled: device.safesearch_enabled,
+            bing: true,
+            duckduckgo: true,
+            google: true,
+            pixabay: true,
+            yandex: true,
+            youtube: true,
+            extra: Default::default(),
+        });
+        if let Some(e) = engines["enabled"].as_bool() {
+            safe_search.enabled = e;
+        }
+        if let Some(b) = engines["bing"].as_bool() {
+            safe_search.bing = b;
+        }
+        if let Some(d) = engines["duckduckgo"].as_bool() {
+            safe_search.duckduckgo = d;
+        }
+        if let Some(g) = engines["google"].as_bool() {
+            safe_search.google = g;
+        }
+        if let Some(p) = engines["pixabay"].as_bool() {
+            safe_search.pixabay = p;
+        }
+        if let Some(y) = engines["yandex"].as_bool() {
+            safe_search.yandex = y;
+        }
+        if let Some(y) = engines["youtube"].as_bool() {
+            safe_search.youtube = y;
+        }
+        if let Some(e) = engines["ecosia"].as_bool() {
+            safe_search
+                .extra
+                .insert("ecosia".to_string(), Value::Bool(e));
+        }
+        device.safesearch_enabled = safe_search.enabled;
+        device.safe_search = Some(safe_search);
+    } else if let Some(safe_search) = device.safe_search.as_mut() {
+        safe_search.enabled = device.safesearch_enabled;
+    }
+
+    Ok(device)
+}
+
+fn parse_ipv4(field: &str, value: Option<&str>) -> Result<Option<Ipv4Addr>, String> {
+    match value.filter(|v| !v.is_empty()) {
+        Some(v) => v
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} is not a valid IPv4 address: {}", field, v)),
+        None => Ok(None),
+    }
+}
+
+/// Gateway and subnet mask of the configured IPv4 network, if both are set.
+fn ipv4_network(status: &DhcpStatus) -> Result<Option<(u32, u32)>, String> {
+    let Some(v4) = &status.v4 else {
+        return Ok(None);
+    };
+    let gateway = parse_ipv4("gateway_ip", v4.gateway_ip.as_deref())?;
+    let mask = parse_ipv4("subnet_mask", v4.subnet_mask.as_deref())?;
+    let (Some(gateway), Some(mask)) = (gateway, mask) else {
+        return Ok(None);
+    };
+
+    let mask = u32::from(mask);
+    if mask == 0 || mask.leading_ones() + mask.trailing_zeros() != 32 {
+        return Err(format!(
+            "subnet_mask {} is not a contiguous netmask",
+            Ipv4Addr::from(mask)
+        ));
+    }
+    Ok(Some((u32::from(gateway), mask)))
+}
+
+fn validate_dhcp_config(status: &DhcpStatus) -> Result<(), String> {
+    if let Some(v4) = &status.v4 {
+        let start = parse_ipv4("range_start", v4.range_start.as_deref())?;
+        let end = parse_ipv4("range_end", v4.range_end.as_deref())?;
+        if let (Some(start), Some(end)) = (start, end) {
+            let (start, end) = (u32::from(start), u32::from(end));
+            if start > end {
+                return Err("range_start must not be after range_end".to_string());
+            }
+            if let Some((gateway, mask)) = ipv4_network(status)? {
+                if start & mask != gateway & mask || end & mask != gateway & mask {
+                    return Err(format!(
+                        "Range {} - {} is outside the gateway subnet {}/{}",
+                        Ipv4Addr::from(start),
+                        Ipv4Addr::from(end),
+                        Ipv4Addr::from(gateway & mask),
+                        mask.leading_ones()
+                    ));
+                }
+                if (start..=end).contains(&gateway) {
+                    return Err(format!(
+                        "Range includes the gateway {}",
+                        Ipv4Addr::from(gateway)
+                    ));
+                }
+            }
+        }
+    }
+    if let Some(start) = status.v6.as_ref().and_then(|v6| v6.range_start.as_deref())
+        && !start.is_empty()
+        && start.parse::<Ipv6Addr>().is_err()
+    {
+        return Err(format!(
+            "v6_range_start is not a valid IPv6 address: {}",
+            start
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects a static lease that collides with another lease or falls outside
+/// the DHCP subnet. With `replacing`, the existing lease with the same MAC is ignored.
+fn validate_static_lease(
+    status: &DhcpStatus,
+    lease: &StaticLease,
+    replacing: bool,
+) -> Result<(), String> {
+    for other in &status.static_leases {
+        let same_mac = other.mac.eq_ignore_ascii_case(&lease.mac);
+        if same_mac && !replacing {
+            return Err(format!(
+                "A static lease for {} already exists ({})",
+                lease.mac, other.ip
+            ));
+        }
+        if !same_mac && other.ip == lease.ip {
+            return Err(format!(
+                "{} is already leased to {} ({})",
+                lease.ip, other.mac, other.hostname
+            ));
+        }
+        if !same_mac
+            && !lease.hostname.is_empty()
+            && other.hostname.eq_ignore_ascii_case(&lease.hostname)
+        {
+            return Err(format!(
+                "Hostname {} is already used by {}",
+                lease.hostname, other.mac
+            ));
+        }
+    }
+    if let Some(other) = status
+        .leases
+        .iter()
+        .find(|l| l.ip == lease.ip && !l.mac.eq_ignore_ascii_case(&lease.mac))
+    {
+        return Err(format!(
+            "{} is currently leased to {} ({})",
+            lease.ip, other.mac, other.hostname
+        ));
+    }
+
+    if let (Ok(ip), Some((gateway, mask))) = (lease.ip.parse::<Ipv4Addr>(), ipv4_network(status)?) {
+        let ip = u32::from(ip);
+        if ip == gateway {
+            return Err(format!("{} is the gateway address", lease.ip));
+        }
+        if ip & mask != gateway & mask {
+            return Err(format!("{} is outside the DHCP subnet", lease.ip));
+        }
+    }
+    Ok(())
+}
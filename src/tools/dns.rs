@@ -1,5 +1,10 @@
-use super::ToolRegistry;
-use crate::adguard::DnsRewrite;
+use super::{ToolRegistry, invalid_params, string_list};
+use crate::adguard::rewrite_analysis;
+use crate::adguard::{
+    AdGuardClient, DnsConfig, DnsRewrite, RewriteDiff, RewriteFormat, UpstreamTestRequest,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 pub fn register(registry: &mut ToolRegistry) {
     registry.register(
@@ -11,10 +16,24 @@ pub fn register(registry: &mut ToolRegistry) {
                 "action": {
                     "type": "string",
                     "description": "DNS action to perform",
-                    "enum": ["list_rewrites", "add_rewrite", "remove_rewrite", "get_config", "set_config", "clear_cache"]
+                    "enum": [
+                        "list_rewrites", "add_rewrite", "remove_rewrite", "update_rewrite",
+                        "import_rewrites", "export_rewrites", "analyze_rewrites", "test_upstreams", "get_config", "set_config", "clear_cache"
+                    ]
                 },
-                "domain": { "type": "string", "description": "Domain for rewrite" },
+                "domain": { "type": "string", "description": "Domain for rewrite; for analyze_rewrites, a name to show the winning rule for" },
                 "answer": { "type": "string", "description": "IP/CNAME for rewrite" },
+                "new_domain": { "type": "string", "description": "Replacement domain for update_rewrite (defaults to domain)" },
+                "new_answer": { "type": "string", "description": "Replacement answer for update_rewrite (defaults to answer)" },
+                "format": { "type": "string", "enum": ["hosts", "dnsmasq", "json"], "default": "json", "description": "Rewrite import/export format" },
+                "data": { "type": "string", "description": "Rewrites to import, in the given format" },
+                "mode": {
+                    "type": "string",
+                    "enum": ["additive-merge", "full-overwrite"],
+                    "default": "additive-merge",
+                    "description": "full-overwrite also removes rewrites missing from the import"
+                },
+                "dry_run": { "type": "boolean", "default": false, "description": "Only show the changes an import would make" },
                 "upstream_dns": { "type": "array", "items": { "type": "string" } },
                 "bootstrap_dns": { "type": "array", "items": { "type": "string" } },
                 "fallback_dns": { "type": "array", "items": { "type": "string" } },
@@ -23,7 +42,27 @@ pub fn register(registry: &mut ToolRegistry) {
                 "cache_size": { "type": "integer" },
                 "cache_ttl_min": { "type": "integer" },
                 "cache_ttl_max": { "type": "integer" },
-                "cache_optimistic": { "type": "boolean" }
+                "cache_optimistic": { "type": "boolean" },
+                "upstream_mode": { "type": "string", "enum": ["load_balance", "parallel", "fastest_addr"] },
+                "upstream_timeout": { "type": "integer", "minimum": 1, "description": "Upstream timeout in seconds" },
+                "blocking_mode": { "type": "string", "enum": ["default", "refused", "nxdomain", "null_ip", "custom_ip"] },
+                "blocking_ipv4": { "type": "string", "description": "IPv4 answer for custom_ip blocking mode" },
+                "blocking_ipv6": { "type": "string", "description": "IPv6 answer for custom_ip blocking mode" },
+                "blocked_response_ttl": { "type": "integer", "minimum": 0 },
+                "ratelimit": { "type": "integer", "minimum": 0, "description": "Requests per second per client subnet (0 disables)" },
+                "ratelimit_subnet_len_ipv4": { "type": "integer", "minimum": 0, "maximum": 32 },
+                "ratelimit_subnet_len_ipv6": { "type": "integer", "minimum": 0, "maximum": 128 },
+                "ratelimit_whitelist": { "type": "array", "items": { "type": "string" } },
+                "dnssec_enabled": { "type": "boolean" },
+                "edns_cs_enabled": { "type": "boolean" },
+                "edns_cs_use_custom": { "type": "boolean" },
+                "edns_cs_custom_ip": { "type": "string" },
+                "disable_ipv6": { "type": "boolean" },
+                "refuse_any": { "type": "boolean" },
+                "use_private_ptr_resolvers": { "type": "boolean" },
+                "local_ptr_upstreams": { "type": "array", "items": { "type": "string" } },
+                "resolve_clients": { "type": "boolean" },
+                "force": { "type": "boolean", "default": false, "description": "Apply set_config even if no upstream answers the test query" }
             },
             "required": ["action"]
         }),
@@ -41,15 +80,89 @@ pub fn register(registry: &mut ToolRegistry) {
                     "add_rewrite" => {
                         let domain = params["domain"].as_str().unwrap_or_default().to_string();
                         let answer = params["answer"].as_str().unwrap_or_default().to_string();
-                        client.add_rewrite(DnsRewrite { domain, answer }).await?;
+                        client.add_rewrite(DnsRewrite { domain, answer, extra: Default::default() }).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Rewrite added" }] }))
                     }
                     "remove_rewrite" => {
                         let domain = params["domain"].as_str().unwrap_or_default().to_string();
                         let answer = params["answer"].as_str().unwrap_or_default().to_string();
-                        client.delete_rewrite(DnsRewrite { domain, answer }).await?;
+                        client.delete_rewrite(DnsRewrite { domain, answer, extra: Default::default() }).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Rewrite removed" }] }))
                     }
+                    "update_rewrite" => {
+                        if params["new_domain"].is_null() && params["new_answer"].is_null() {
+                            return Err(invalid_params("update_rewrite needs new_domain or new_answer".to_string()));
+                        }
+                        let domain = params["domain"].as_str().unwrap_or_default().to_string();
+                        let answer = params["answer"].as_str().unwrap_or_default().to_string();
+                        let wanted = DnsRewrite { domain, answer, extra: Default::default() };
+                        // Take the server's entry so fields this client does not model are kept
+                        let Some(target) = client.list_rewrites().await?.into_iter().find(|r| *r == wanted) else {
+                            return Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Rewrite not found" }], "isError": true }));
+                        };
+                        let update = DnsRewrite {
+                            domain: params["new_domain"].as_str().unwrap_or(&target.domain).to_string(),
+                            answer: params["new_answer"].as_str().unwrap_or(&target.answer).to_string(),
+                            extra: target.extra.clone(),
+                        };
+                        if update == target {
+                            return Err(invalid_params(format!("Rewrite is already {} -> {}", target.domain, target.answer)));
+                        }
+                        let text = format!("Rewrite updated: {} -> {}", update.domain, update.answer);
+                        client.update_rewrite(target, update).await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "import_rewrites" => {
+                        let format = rewrite_format(&params)?;
+                        let (desired, warnings) = format
+                            .parse_with_warnings(params["data"].as_str().unwrap_or_default())
+                            .map_err(|e| invalid_params(format!("Invalid rewrites: {}", e)))?;
+                        let skipped: String = warnings.iter().map(|w| format!("\nSkipped {}", w)).collect();
+                        let overwrite = params["mode"].as_str() == Some("full-overwrite");
+                        let current = client.list_rewrites().await?;
+                        let diff = RewriteDiff::compute(&current, &desired, overwrite);
+
+                        if params["dry_run"].as_bool().unwrap_or(false) {
+                            return Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("Dry run: {}{}", diff, skipped) }] }));
+                        }
+                        // Add before removing so names stay resolvable during the import
+                        for rewrite in &diff.add {
+                            client.add_rewrite(rewrite.clone()).await?;
+                        }
+                        for (target, update) in &diff.update {
+                            client.update_rewrite(target.clone(), update.clone()).await?;
+                        }
+                        for rewrite in &diff.remove {
+                            client.delete_rewrite(rewrite.clone()).await?;
+                        }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("Rewrites imported: {}{}", diff, skipped) }] }))
+                    }
+                    "export_rewrites" => {
+                        let format = rewrite_format(&params)?;
+                        let rewrites = client.list_rewrites().await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": format.format(&rewrites) }] }))
+                    }
+                    "analyze_rewrites" => {
+                        let analysis = client.analyze_rewrites().await?;
+                        let mut text = analysis.to_string();
+                        if let Some(domain) = params["domain"].as_str() {
+                            let rewrites = client.list_rewrites().await?;
+                            text.push_str(&format!("\n{}", rewrite_analysis::resolve(&rewrites, domain)));
+                        }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "test_upstreams" => {
+                        let current = client.get_dns_info().await?;
+                        let upstreams = UpstreamTestRequest {
+                            upstream_dns: string_list(&params["upstream_dns"]).unwrap_or(current.upstream_dns),
+                            bootstrap_dns: string_list(&params["bootstrap_dns"]).unwrap_or(current.bootstrap_dns),
+                            fallback_dns: string_list(&params["fallback_dns"]).unwrap_or(current.fallback_dns),
+                        };
+                        let results = client.test_upstream_dns(&upstreams).await?;
+                        let mut text = format_upstream_results(&results);
+                        text.push_str(&rank_upstreams(&client).await);
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
                     "get_config" => {
                         let config = client.get_dns_info().await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&config)? }] }))
@@ -71,7 +184,50 @@ pub fn register(registry: &mut ToolRegistry) {
                         if let Some(min) = params["cache_ttl_min"].as_u64() { config.cache_ttl_min = min as u32; }
                         if let Some(max) = params["cache_ttl_max"].as_u64() { config.cache_ttl_max = max as u32; }
                         if let Some(o) = params["cache_optimistic"].as_bool() { config.cache_optimistic = o; }
+                        if let Some(m) = params["upstream_mode"].as_str() { config.upstream_mode = m.to_string(); }
+                        if let Some(t) = params["upstream_timeout"].as_u64() { config.upstream_timeout = Some(t as u32); }
+                        if let Some(m) = params["blocking_mode"].as_str() { config.blocking_mode = Some(m.to_string()); }
+                        if let Some(ip) = params["blocking_ipv4"].as_str() { config.blocking_ipv4 = Some(ip.to_string()); }
+                        if let Some(ip) = params["blocking_ipv6"].as_str() { config.blocking_ipv6 = Some(ip.to_string()); }
+                        if let Some(t) = params["blocked_response_ttl"].as_u64() { config.blocked_response_ttl = Some(t as u32); }
+                        if let Some(r) = params["ratelimit"].as_u64() { config.ratelimit = Some(r as u32); }
+                        if let Some(l) = params["ratelimit_subnet_len_ipv4"].as_u64() { config.ratelimit_subnet_len_ipv4 = Some(l as u32); }
+                        if let Some(l) = params["ratelimit_subnet_len_ipv6"].as_u64() { config.ratelimit_subnet_len_ipv6 = Some(l as u32); }
+                        if let Some(w) = params["ratelimit_whitelist"].as_array() {
+                            config.ratelimit_whitelist = Some(w.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect());
+                        }
+                        if let Some(d) = params["dnssec_enabled"].as_bool() { config.dnssec_enabled = Some(d); }
+                        if let Some(e) = params["edns_cs_enabled"].as_bool() { config.edns_cs_enabled = Some(e); }
+                        if let Some(c) = params["edns_cs_use_custom"].as_bool() { config.edns_cs_use_custom = Some(c); }
+                        if let Some(ip) = params["edns_cs_custom_ip"].as_str() { config.edns_cs_custom_ip = Some(ip.to_string()); }
+                        if let Some(d) = params["disable_ipv6"].as_bool() { config.disable_ipv6 = Some(d); }
+                        if let Some(r) = params["refuse_any"].as_bool() { config.refuse_any = Some(r); }
+                        if let Some(p) = params["use_private_ptr_resolvers"].as_bool() { config.use_private_ptr_resolvers = p; }
+                        if let Some(l) = params["local_ptr_upstreams"].as_array() {
+                            config.local_ptr_upstreams = l.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
+                        }
+                        if let Some(r) = params["resolve_clients"].as_bool() { config.resolve_clients = Some(r); }
 
+                        validate_dns_config(&config)?;
+                        let upstreams_changed = ["upstream_dns", "bootstrap_dns", "fallback_dns"]
+                            .iter()
+                            .any(|key| params[*key].is_array());
+                        if upstreams_changed && !params["force"].as_bool().unwrap_or(false) {
+                            let results = client
+                                .test_upstream_dns(&UpstreamTestRequest {
+                                    upstream_dns: config.upstream_dns.clone(),
+                                    bootstrap_dns: config.bootstrap_dns.clone(),
+                                    fallback_dns: config.fallback_dns.clone(),
+                                })
+                                .await?;
+                            if !upstream_works(&results, &config.upstream_dns) {
+                                let text = format!(
+                                    "DNS config not applied: no upstream works (set force to apply anyway)\n{}",
+                                    format_upstream_results(&results)
+                                );
+                                return Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }], "isError": true }));
+                            }
+                        }
                         client.set_dns_config(config).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "DNS config updated" }] }))
                     }
@@ -89,3 +245,121 @@ pub fn register(registry: &mut ToolRegistry) {
         },
     );
 }
+
+/// Whether an `upstream_dns` entry passed the test. Bootstrap and fallback
+/// results do not count: they cannot stand in for the main upstreams.
+fn upstream_works(results: &HashMap<String, String>, upstream_dns: &[String]) -> bool {
+    results.iter().any(|(upstream, status)| {
+        status == "OK"
+            && upstream_dns.iter().any(|entry| {
+                // Domain-specific entries look like "[/lan/]192.168.1.1 9.9.9.9"
+                entry
+                    .rsplit(']')
+                    .next()
+                    .is_some_and(|addresses| addresses.split_whitespace().any(|a| a == upstream))
+            })
+    })
+}
+
+fn format_upstream_results(results: &HashMap<String, String>) -> String {
+    let mut sorted: Vec<_> = results.iter().collect();
+    sorted.sort();
+    let working = sorted.iter().filter(|(_, status)| *status == "OK").count();
+    let mut text = format!("{} of {} upstreams work:\n", working, sorted.len());
+    for (upstream, status) in sorted {
+        text.push_str(&format!("- {}: {}\n", upstream, status));
+    }
+    text
+}
+
+/// Ranks the upstreams in use by their average response time over the stats period.
+async fn rank_upstreams(client: &AdGuardClient) -> String {
+    let stats = match client.get_stats(None).await {
+        Ok(stats) => stats,
+        Err(e) => return format!("\nUpstream ranking unavailable: {}\n", e),
+    };
+    let mut times: Vec<(&String, f64)> = stats
+        .top_upstreams_avg_time
+        .iter()
+        .flatten()
+        .map(|(upstream, time)| (upstream, *time))
+        .collect();
+    if times.is_empty() {
+        return String::new();
+    }
+    times.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut text = "\nCurrent upstreams by average response time:\n".to_string();
+    for (rank, (upstream, time)) in times.iter().enumerate() {
+        text.push_str(&format!(
+            "{}. {}: {:.2}ms\n",
+            rank + 1,
+            upstream,
+            time * 1000.0
+        ));
+    }
+    text
+}
+
+fn rewrite_format(params: &serde_json::Value) -> crate::error::Result<RewriteFormat> {
+    let name = params["format"].as_str().unwrap_or("json");
+    RewriteFormat::from_name(name)
+        .ok_or_else(|| invalid_params(format!("Unknown rewrite format: {}", name)))
+}
+
+fn validate_dns_config(config: &DnsConfig) -> crate::error::Result<()> {
+    if let Some(mode) = config.blocking_mode.as_deref() {
+        if !["default", "refused", "nxdomain", "null_ip", "custom_ip"].contains(&mode) {
+            return Err(invalid_params(format!("Unknown blocking_mode: {}", mode)));
+        }
+        if mode == "custom_ip" {
+            let v4 = config.blocking_ipv4.as_deref().unwrap_or_default();
+            let v6 = config.blocking_ipv6.as_deref().unwrap_or_default();
+            if v4.parse::<Ipv4Addr>().is_err() || v6.parse::<Ipv6Addr>().is_err() {
+                return Err(invalid_params(
+                    "custom_ip blocking mode requires valid blocking_ipv4 and blocking_ipv6"
+                        .to_string(),
+                ));
+            }
+        }
+    }
+    if let Some(len) = config.ratelimit_subnet_len_ipv4
+        && len > 32
+    {
+        return Err(invalid_params(format!(
+            "ratelimit_subnet_len_ipv4 out of range: {}",
+            len
+        )));
+    }
+    if let Some(len) = config.ratelimit_subnet_len_ipv6
+        && len > 128
+    {
+        return Err(invalid_params(format!(
+            "ratelimit_subnet_len_ipv6 out of range: {}",
+            len
+        )));
+    }
+    for ip in config.ratelimit_whitelist.iter().flatten() {
+        if ip.parse::<IpAddr>().is_err() {
+            return Err(invalid_params(format!(
+                "Invalid ratelimit_whitelist address: {}",
+                ip
+            )));
+        }
+    }
+    if config.edns_cs_use_custom == Some(true) {
+        let custom = config.edns_cs_custom_ip.as_deref().unwrap_or_default();
+        if custom.parse::<IpAddr>().is_err() {
+            return Err(invalid_params(format!(
+                "Invalid edns_cs_custom_ip: {}",
+                custom
+            )));
+        }
+    }
+    if config.cache_ttl_max > 0 && config.cache_ttl_min > config.cache_ttl_max {
+        return Err(invalid_params(
+            "cache_ttl_min must not exceed cache_ttl_max".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
-use super::ToolRegistry;
+use super::{ToolRegistry, invalid_params};
+use crate::adguard::WeeklySchedule;
 
 pub fn register(registry: &mut ToolRegistry) {
     registry.register(
@@ -13,20 +14,37 @@ pub fn register(registry: &mut ToolRegistry) {
                     "enum": [
                         "list_filters", "add_filter", "remove_filter", "update_filter", "toggle_filter",
                         "list_custom_rules", "set_custom_rules", "add_custom_rule", "remove_custom_rule",
-                        "list_blocked_services", "toggle_blocked_service", "check_host"
+                        "list_blocked_services", "toggle_blocked_service", "check_host",
+                        "get_blocked_services_schedule", "set_blocked_services_schedule",
+                        "refresh_filters", "set_filtering_config", "preview_filter"
                     ]
                 },
+                "scope": {
+                    "type": "string",
+                    "enum": ["all", "blocklists", "allowlists", "url"],
+                    "default": "all",
+                    "description": "Lists to re-download for refresh_filters; 'url' refreshes the blocklists or allowlists containing the list given in 'url'"
+                },
+                "interval": {
+                    "type": "integer",
+                    "enum": [0, 1, 12, 24, 72, 168],
+                    "description": "Filter list update interval in hours (0 disables automatic updates)"
+                },
                 "identifier": { "type": "string", "description": "Filter list Name, ID, or URL" },
                 "name": { "type": "string", "description": "Filter list name" },
                 "url": { "type": "string", "description": "Filter list URL" },
                 "new_name": { "type": "string" },
                 "new_url": { "type": "string" },
                 "whitelist": { "type": "boolean", "default": false },
-                "enabled": { "type": "boolean" },
+                "enabled": { "type": "boolean", "description": "Filter list state, or filtering as a whole for set_filtering_config" },
                 "rule": { "type": "string", "description": "Custom rule text" },
                 "rules": { "type": "array", "items": { "type": "string" } },
                 "service_id": { "type": "string", "description": "Service ID (e.g., 'youtube')" },
                 "blocked": { "type": "boolean" },
+                "schedule": {
+                    "type": "object",
+                    "description": "Weekly times when service blocking is paused; times in \"HH:MM\" or ms since midnight, e.g. {\"time_zone\": \"Europe/Berlin\", \"sat\": {\"start\": \"08:00\", \"end\": \"20:00\"}}. An object without days removes all pauses."
+                },
                 "domain": { "type": "string" },
                 "client": { "type": "string", "description": "Optional client IP/Name" }
             },
@@ -138,6 +156,89 @@ pub fn register(registry: &mut ToolRegistry) {
                         else if !blocked && exists { current.retain(|x| x != &id); client.set_blocked_services(current).await?; }
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Service toggled" }] }))
                     }
+                    "get_blocked_services_schedule" => {
+                        let config = client.get_blocked_services_config().await?;
+                        let text = config.schedule.unwrap_or_default().to_string();
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "set_blocked_services_schedule" => {
+                        let mut schedule = WeeklySchedule::from_params(&params["schedule"])
+                            .map_err(|e| invalid_params(format!("Invalid schedule: {}", e)))?;
+                        if schedule.time_zone.is_empty() {
+                            schedule.time_zone = "Local".to_string();
+                        }
+                        let mut config = client.get_blocked_services_config().await?;
+                        config.schedule = Some(schedule.clone());
+                        client.set_blocked_services_config(config).await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("Schedule updated\n{}", schedule) }] }))
+                    }
+                    "refresh_filters" => {
+                        let scope = params["scope"].as_str().unwrap_or("all");
+                        let text = match scope {
+                            "all" => {
+                                let blocklists = client.refresh_filters(false).await?;
+                                let allowlists = client.refresh_filters(true).await?;
+                                format!("Filters refreshed: {} blocklists and {} allowlists updated", blocklists, allowlists)
+                            }
+                            "blocklists" => format!("Filters refreshed: {} blocklists updated", client.refresh_filters(false).await?),
+                            "allowlists" => format!("Filters refreshed: {} allowlists updated", client.refresh_filters(true).await?),
+                            "url" => {
+                                let url = params["url"].as_str().unwrap_or_default();
+                                let config = client.list_filters().await?;
+                                let Some(f) = config.filters.iter().chain(config.whitelist_filters.iter()).find(|f| f.url == url) else {
+                                    return Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Filter not found" }], "isError": true }));
+                                };
+                                if !f.enabled {
+                                    return Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("Filter '{}' is disabled and is not downloaded", f.name) }], "isError": true }));
+                                }
+                                let is_white = config.whitelist_filters.iter().any(|wf| wf.url == f.url);
+                                // AdGuard Home has no per-list refresh, so refresh every list of the same kind
+                                let updated = client.refresh_filters(is_white).await?;
+                                let kind = if is_white { "allowlists" } else { "blocklists" };
+                                format!("Filter '{}' refreshed along with the other {}: {} updated", f.name, kind, updated)
+                            }
+                            other => return Err(invalid_params(format!("Unknown refresh scope: {}", other))),
+                        };
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "set_filtering_config" => {
+                        let current = client.list_filters().await?;
+                        let enabled = params["enabled"].as_bool().unwrap_or(current.enabled);
+                        let interval = match params["interval"].as_u64() {
+                            Some(hours) if [0, 1, 12, 24, 72, 168].contains(&hours) => hours as u32,
+                            Some(hours) => return Err(invalid_params(format!(
+                                "Invalid update interval: {} hours (expected 0, 1, 12, 24, 72 or 168)", hours
+                            ))),
+                            None => current.interval,
+                        };
+                        client.set_filtering_config(enabled, interval).await?;
+                        let schedule = if interval == 0 { "never".to_string() } else { format!("every {} hours", interval) };
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!(
+                            "Filtering {}; lists update {}", if enabled { "enabled" } else { "disabled" }, schedule
+                        ) }] }))
+                    }
+                    "preview_filter" => {
+                        let url = params["url"].as_str().unwrap_or_default();
+                        if url.is_empty() {
+                            return Err(invalid_params("url is required".to_string()));
+                        }
+                        let preview = client.preview_filter(url).await?;
+                        let mut text = format!(
+                            "{}\n{} rules ({} bytes, {} comment lines)\n",
+                            preview.title.as_deref().unwrap_or(url), preview.rules, preview.bytes, preview.comments
+                        );
+                        text.push_str(&format!(
+                            "- adblock: {}\n- exceptions: {}\n- hosts: {}\n- plain domains: {}\n- regex: {}\n- cosmetic (ignored by AdGuard Home): {}\n- other: {}\n",
+                            preview.adblock, preview.exceptions, preview.hosts, preview.domains, preview.regex, preview.cosmetic, preview.other
+                        ));
+                        if !preview.samples.is_empty() {
+                            text.push_str("Sample rules:\n");
+                            for rule in &preview.samples {
+                                text.push_str(&format!("  {}\n", rule));
+                            }
+                        }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
                     "check_host" => {
                         let domain = params["domain"].as_str().unwrap_or_default();
                         let cl = params["client"].as_str();
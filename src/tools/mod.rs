@@ -13,6 +13,54 @@ use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::Arc;
 
+pub(crate) fn invalid_params(message: String) -> crate::error::Error {
+    crate::error::Error::Mcp(crate::mcp::ResponseError {
+        code: -32602,
+        message,
+        data: None,
+    })
+}
+
+/// The strings in a JSON array parameter, or `None` if it is not an array.
+pub(crate) fn string_list(value: &Value) -> Option<Vec<String>> {
+    value.as_array().map(|items| {
+        items
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect()
+    })
+}
+
+/// Replaces every PEM private key block in `text`, so keys never reach tool output.
+pub(crate) fn redact_private_keys(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("-----BEGIN ") {
+        let label_start = start + "-----BEGIN ".len();
+        let Some(label_len) = rest[label_start..].find("-----") else {
+            break;
+        };
+        let label = &rest[label_start..label_start + label_len];
+        let header_end = label_start + label_len + "-----".len();
+        if !label.ends_with("PRIVATE KEY") {
+            out.push_str(&rest[..header_end]);
+            rest = &rest[header_end..];
+            continue;
+        }
+
+        out.push_str(&rest[..start]);
+        out.push_str(crate::adguard::REDACTED);
+        let footer = format!("-----END {}-----", label);
+        rest = match rest[header_end..].find(&footer) {
+            Some(end) => &rest[header_end + end + footer.len()..],
+            // Truncated key: drop everything after the header
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
 pub type ToolHandler = dyn Fn(
         &AdGuardClient,
         &AppConfig,
@@ -60,7 +108,18 @@ impl ToolRegistry {
             description: description.to_string(),
             input_schema,
             handler: Arc::new(move |client, config, params| {
-                Box::pin(handler(client, config, params))
+                let result = handler(client, config, params);
+                Box::pin(async move {
+                    let mut result = result.await?;
+                    if let Some(content) = result["content"].as_array_mut() {
+                        for item in content {
+                            if let Some(text) = item["text"].as_str() {
+                                item["text"] = Value::String(redact_private_keys(text));
+                            }
+                        }
+                    }
+                    Ok(result)
+                })
             }),
         };
         self.tools.insert(name.to_string(), tool);
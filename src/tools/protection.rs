@@ -1,4 +1,6 @@
 use super::ToolRegistry;
+use crate::adguard::TlsConfig;
+use chrono::{DateTime, Utc};
 
 pub fn register(registry: &mut ToolRegistry) {
     registry.register(
@@ -10,7 +12,10 @@ pub fn register(registry: &mut ToolRegistry) {
                 "action": {
                     "type": "string",
                     "description": "Protection action to perform",
-                    "enum": ["get_config", "set_config", "toggle_feature", "get_tls_config", "set_tls_config"]
+                    "enum": [
+                        "get_config", "set_config", "toggle_feature", "get_tls_config", "set_tls_config",
+                        "get_certificate_info", "check_certificate_expiry"
+                    ]
                 },
                 "feature": {
                     "type": "string",
@@ -45,12 +50,17 @@ pub fn register(registry: &mut ToolRegistry) {
                 "certificate_chain": { "type": "string" },
                 "private_key": { "type": "string" },
                 "certificate_path": { "type": "string" },
-                "private_key_path": { "type": "string" }
+                "private_key_path": { "type": "string" },
+                "warn_days": {
+                    "type": "integer", "minimum": 0, "default": 30,
+                    "description": "check_certificate_expiry: warn about certificates expiring within this many days"
+                }
             },
             "required": ["action"]
         }),
-        |client, _config, params| {
+        |client, config, params| {
             let client = client.clone();
+            let config = config.clone();
             let params = params.unwrap_or_default();
             let action = params["action"].as_str().unwrap_or_default().to_string();
 
@@ -106,8 +116,45 @@ pub fn register(registry: &mut ToolRegistry) {
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": format!("Feature '{}' set to {}", feature, enabled) }] }))
                     }
                     "get_tls_config" => {
-                        let config = client.get_tls_status().await?;
-                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&config)? }] }))
+                        let tls = client.get_tls_status().await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&tls.redacted())? }] }))
+                    }
+                    "get_certificate_info" => {
+                        let tls = client.get_tls_status().await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": format_certificate(&tls, Utc::now()) }] }))
+                    }
+                    "check_certificate_expiry" => {
+                        let warn_days = params["warn_days"].as_i64().unwrap_or(30);
+                        let now = Utc::now();
+                        let mut text = format!("Certificates expiring within {} days:\n", warn_days);
+                        let mut warnings = 0;
+                        for (i, instance) in config.instances.iter().enumerate() {
+                            let name = instance.name.clone().unwrap_or_else(|| format!("instance {}", i));
+                            let status = match config.clients.get(instance) {
+                                Ok(instance_client) => instance_client.get_tls_status().await,
+                                Err(e) => Err(e),
+                            };
+                            let line = match status {
+                                Err(e) => format!("- {}: could not read TLS status: {}", name, e),
+                                Ok(tls) if !tls.enabled => format!("- {}: encryption disabled", name),
+                                Ok(tls) => match tls.days_until_expiry(now) {
+                                    None => format!("- {}: no certificate expiry reported", name),
+                                    Some(days) if days < 0 => {
+                                        warnings += 1;
+                                        format!("- {}: EXPIRED {} days ago", name, -days)
+                                    }
+                                    Some(days) if days <= warn_days => {
+                                        warnings += 1;
+                                        format!("- {}: WARNING expires in {} days", name, days)
+                                    }
+                                    Some(days) => format!("- {}: OK, expires in {} days", name, days),
+                                },
+                            };
+                            text.push_str(&line);
+                            text.push('\n');
+                        }
+                        text.push_str(&format!("{} warnings\n", warnings));
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
                     }
                     "set_tls_config" => {
                         let mut config = client.get_tls_status().await?;
@@ -121,6 +168,14 @@ pub fn register(registry: &mut ToolRegistry) {
                         if let Some(k) = params["private_key"].as_str() { config.private_key = k.to_string(); }
                         if let Some(cp) = params["certificate_path"].as_str() { config.certificate_path = cp.to_string(); }
                         if let Some(kp) = params["private_key_path"].as_str() { config.private_key_path = kp.to_string(); }
+                        // AdGuard Home reports certificate problems from validate, not configure
+                        let checked = client.validate_tls(config.clone()).await?;
+                        if config.enabled
+                            && let Some(warning) = checked.warning_validation.filter(|w| !w.is_empty())
+                        {
+                            let text = format!("TLS config not applied: {}", warning);
+                            return Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }], "isError": true }));
+                        }
                         client.configure_tls(config).await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "TLS config updated" }] }))
                     }
@@ -134,3 +189,54 @@ pub fn register(registry: &mut ToolRegistry) {
         },
     );
 }
+
+fn format_certificate(tls: &TlsConfig, now: DateTime<Utc>) -> String {
+    let mut text = format!(
+        "Encryption: {}\n",
+        if tls.enabled { "enabled" } else { "disabled" }
+    );
+    if !tls.server_name.is_empty() {
+        text.push_str(&format!("Server name: {}\n", tls.server_name));
+    }
+    let Some(not_after) = tls.not_after.as_deref() else {
+        text.push_str("No certificate configured.\n");
+        return text;
+    };
+    if let Some(subject) = &tls.subject {
+        text.push_str(&format!("Subject: {}\n", subject));
+    }
+    if let Some(issuer) = &tls.issuer {
+        text.push_str(&format!("Issuer: {}\n", issuer));
+    }
+    if !tls.dns_names.is_empty() {
+        text.push_str(&format!("DNS names: {}\n", tls.dns_names.join(", ")));
+    }
+    if let Some(key_type) = &tls.key_type {
+        text.push_str(&format!("Key type: {}\n", key_type));
+    }
+    text.push_str(&format!(
+        "Valid: {} to {}\n",
+        tls.not_before.as_deref().unwrap_or("?"),
+        not_after
+    ));
+    match tls.days_until_expiry(now) {
+        Some(days) if days < 0 => text.push_str(&format!("EXPIRED {} days ago\n", -days)),
+        Some(days) => text.push_str(&format!("Expires in {} days\n", days)),
+        None => {}
+    }
+    text.push_str(&format!(
+        "Checks: chain {}, certificate {}, key {}, pair {}\n",
+        if tls.valid_chain.unwrap_or(false) {
+            "ok"
+        } else {
+            "invalid"
+        },
+        if tls.valid_cert { "ok" } else { "invalid" },
+        if tls.valid_key { "ok" } else { "invalid" },
+        if tls.valid_pair { "ok" } else { "invalid" },
+    ));
+    if let Some(warning) = tls.warning_validation.as_deref().filter(|w| !w.is_empty()) {
+        text.push_str(&format!("Warning: {}\n", warning));
+    }
+    text
+}
@@ -1,32 +1,62 @@
 use crate::adguard::AdGuardClient;
 use crate::error::Result;
-use crate::sync::SyncState;
-use crate::tools::ToolRegistry;
+use crate::sync::history::format_status;
+use crate::sync::{Drift, ModuleSelection, SYNC_MODULES, SyncHistoryStore, SyncState};
+use crate::tools::{ToolRegistry, string_list};
+use futures::stream::{self, StreamExt};
 use serde_json::{Value, json};
 
 pub fn register(registry: &mut ToolRegistry) {
     registry.register(
         "sync_instances",
-        "Synchronize configuration from the master instance to one or more replica instances.",
+        "Synchronize configuration from the master instance to one or more replica instances, show recent sync results, or check replicas for drift.",
         json!({
             "type": "object",
             "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["sync", "status", "drift"],
+                    "description": "sync (default) pushes to replicas; status shows each replica's recent sync results; drift compares replicas with the master and flags changes made directly on a replica."
+                },
                 "replicas": {
                     "type": "array",
                     "items": {
-                        "type": "object",
-                        "properties": {
-                            "url": { "type": "string", "description": "URL of the replica AdGuard Home instance." },
-                            "api_key": { "type": "string", "description": "API Key for the replica." }
-                        },
-                        "required": ["url", "api_key"]
+                        "anyOf": [
+                            { "type": "string", "description": "Name of a configured instance." },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "url": { "type": "string", "description": "URL of the replica AdGuard Home instance." },
+                                    "api_key": { "type": "string", "description": "API Key for the replica." }
+                                },
+                                "required": ["url", "api_key"]
+                            }
+                        ]
                     },
-                    "description": "Optional list of replicas to sync to. Defaults to configured replicas."
+                    "description": "Optional list of replicas to sync to: configured instance names or URL/API key pairs. Defaults to configured replicas."
                 },
                 "mode": {
                     "type": "string",
                     "enum": ["additive-merge", "full-overwrite"],
-                    "description": "Sync mode. Defaults to configured default mode."
+                    "description": "Sync mode. Defaults to each replica's configured mode."
+                },
+                "include_modules": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": SYNC_MODULES.map(|(key, _)| key) },
+                    "description": "Sync only these modules. Overrides the replicas' configured lists."
+                },
+                "exclude_modules": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": SYNC_MODULES.map(|(key, _)| key) },
+                    "description": "Modules to leave out (default: dhcp, tls, profile). Overrides the replicas' configured lists."
+                },
+                "dry_run": {
+                    "type": "boolean",
+                    "description": "Fetch each replica and report the per-module changes a sync would make, without applying them."
+                },
+                "transactional": {
+                    "type": "boolean",
+                    "description": "Snapshot each replica first and restore it if a critical module fails. Defaults to each replica's configured setting."
                 }
             }
         }),
@@ -43,11 +73,15 @@ async fn sync_instances(
     config: &crate::config::AppConfig,
     args: Option<Value>,
 ) -> Result<Value> {
-    let mode = args
+    let action = args
         .as_ref()
-        .and_then(|a| a.get("mode"))
+        .and_then(|a| a.get("action"))
         .and_then(|v| v.as_str())
-        .unwrap_or(config.default_sync_mode.as_str());
+        .unwrap_or("sync");
+    let mode_override = args
+        .as_ref()
+        .and_then(|a| a.get("mode"))
+        .and_then(|v| v.as_str());
 
     let replicas = if let Some(r) = args
         .as_ref()
@@ -55,16 +89,58 @@ async fn sync_instances(
         .and_then(|v| v.as_array())
     {
         r.iter()
-            .filter_map(|v| {
-                let url = v.get("url")?.as_str()?.to_string();
-                let api_key = v.get("api_key")?.as_str()?.to_string();
-                Some(crate::config::ReplicaConfig { url, api_key })
+            .filter_map(|v| match v {
+                Value::String(name) => Some(crate::config::ReplicaConfig {
+                    instance: Some(name.clone()),
+                    ..Default::default()
+                }),
+                _ => {
+                    let url = v.get("url")?.as_str()?.to_string();
+                    let api_key = v.get("api_key")?.as_str()?.to_string();
+                    Some(crate::config::ReplicaConfig {
+                        url,
+                        api_key,
+                        ..Default::default()
+                    })
+                }
             })
             .collect::<Vec<_>>()
     } else {
         config.replicas.clone()
     };
 
+    let dry_run = args
+        .as_ref()
+        .and_then(|a| a.get("dry_run"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let transactional_override = args
+        .as_ref()
+        .and_then(|a| a.get("transactional"))
+        .and_then(|v| v.as_bool());
+    let sync_action = match action {
+        "sync" if dry_run => SyncAction::Preview,
+        "sync" => SyncAction::Push,
+        "drift" => SyncAction::Drift,
+        "status" => {
+            return Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": sync_status(config, &replicas).await
+                }]
+            }));
+        }
+        _ => {
+            return Err(super::invalid_params(format!("Unknown action: {}", action)));
+        }
+    };
+    let include_override = args
+        .as_ref()
+        .and_then(|a| string_list(&a["include_modules"]));
+    let exclude_override = args
+        .as_ref()
+        .and_then(|a| string_list(&a["exclude_modules"]));
+
     if replicas.is_empty() {
         return Ok(json!({
             "content": [{
@@ -80,43 +156,65 @@ async fn sync_instances(
         .await
         .map_err(|e| crate::error::Error::Generic(e.to_string()))?;
 
-    let mut results = Vec::new();
-
-    // 2. Push to Replicas
+    // 2. Resolve every replica first, so bad arguments fail before anything is pushed
+    let mut jobs = Vec::new();
     for replica_config in replicas {
-        // Parse URL to host and port
-        let url = replica_config.url.clone();
-        let _parsed_url =
-            url::Url::parse(&url).map_err(|e| crate::error::Error::Config(e.to_string()))?;
-
-        let replica_instance = crate::config::InstanceConfig {
-            name: Some("replica".to_string()),
-            url: url.clone(),
-            username: Some("admin".to_string()),
-            password: Some(replica_config.api_key.clone()),
-            ..Default::default()
-        };
-
-        let replica_client = AdGuardClient::new(replica_instance);
+        let replica_instance = config
+            .replica_instance(&replica_config)
+            .map_err(super::invalid_params)?;
+        let replica_client = config.clients.get(&replica_instance)?;
 
-        match master_state.push_to_replica(&replica_client, mode).await {
-            Ok(result) => {
-                let mut msg = format!("Replica {}: ", url);
-                if result.success {
-                    msg.push_str("Successfully synced.");
-                } else {
-                    msg.push_str(&format!(
-                        "Synced with errors. Failed modules: {}. Errors: {}",
-                        result.failed_modules.join(", "),
-                        result.errors.join("; ")
-                    ));
-                }
-                results.push(msg);
-            }
-            Err(e) => results.push(format!("Failed to connect or push to {}: {}", url, e)),
+        let selection = if include_override.is_some() || exclude_override.is_some() {
+            ModuleSelection::new(
+                include_override.clone().unwrap_or_default(),
+                exclude_override.clone(),
+            )
+        } else {
+            ModuleSelection::new(
+                replica_config.include_modules.clone(),
+                replica_config.exclude_modules.clone(),
+            )
         }
+        .map_err(super::invalid_params)?;
+        let critical = match transactional_override.unwrap_or(replica_config.transactional) {
+            true => Some(
+                replica_config
+                    .critical_selection()
+                    .map_err(super::invalid_params)?,
+            ),
+            false => None,
+        };
+
+        jobs.push((
+            replica_config.name().to_string(),
+            replica_client,
+            selection,
+            critical,
+            mode_override
+                .unwrap_or(config.replica_mode(&replica_config))
+                .to_string(),
+        ));
     }
 
+    // 3. Push to Replicas, up to `sync_concurrency` at a time
+    let master_state = &master_state;
+    let results: Vec<String> = stream::iter(jobs)
+        .map(
+            |(name, replica_client, selection, critical, mode)| async move {
+                let job = ReplicaJob {
+                    name: &name,
+                    client: &replica_client,
+                    selection: &selection,
+                    critical: critical.as_ref(),
+                    mode: &mode,
+                };
+                sync_replica(master_state, &job, sync_action, &config.sync_history).await
+            },
+        )
+        .buffered(config.sync_concurrency.max(1))
+        .collect()
+        .await;
+
     Ok(json!({
         "content": [{
             "type": "text",
@@ -125,3 +223,150 @@ async fn sync_instances(
         }]
     }))
 }
+
+/// One replica's share of a `sync_instances` call.
+struct ReplicaJob<'a> {
+    name: &'a str,
+    client: &'a AdGuardClient,
+    selection: &'a ModuleSelection,
+    /// Set in transactional mode: failures of these modules roll the replica back.
+    critical: Option<&'a ModuleSelection>,
+    mode: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncAction {
+    Push,
+    Preview,
+    Drift,
+}
+
+/// Recent results for the given replicas, or for every configured and
+/// previously synced replica.
+async fn sync_status(
+    config: &crate::config::AppConfig,
+    replicas: &[crate::config::ReplicaConfig],
+) -> String {
+    let mut names: Vec<String> = replicas.iter().map(|r| r.name().to_string()).collect();
+    if replicas == config.replicas.as_slice() {
+        for name in config.sync_history.replicas().await {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    if names.is_empty() {
+        return "No replicas configured and no sync history recorded.".to_string();
+    }
+
+    let mut lines = Vec::new();
+    for name in names {
+        lines.push(format_status(&name, &config.sync_history.get(&name).await));
+    }
+    lines.join("\n")
+}
+
+async fn sync_replica(
+    master_state: &SyncState,
+    job: &ReplicaJob<'_>,
+    action: SyncAction,
+    history: &SyncHistoryStore,
+) -> String {
+    let &ReplicaJob {
+        name,
+        client: replica_client,
+        selection,
+        critical,
+        mode,
+    } = job;
+    if action != SyncAction::Push {
+        let replica_state = match SyncState::fetch(replica_client).await {
+            Ok(replica_state) => replica_state,
+            Err(e) => return format!("Failed to fetch state of {}: {}", name, e),
+        };
+        let plan = master_state.plan(&replica_state, mode, selection);
+        if action == SyncAction::Preview {
+            return format!("Replica {} (dry run, nothing applied): {}", name, plan);
+        }
+        if plan.is_empty() {
+            return format!("Replica {}: no drift, matches the master.", name);
+        }
+
+        let drift = Drift::classify(
+            &plan,
+            &master_state.fingerprints(),
+            &history.get(name).await,
+        );
+        let mut msg = format!(
+            "Replica {}: {} module(s) differ from the master.",
+            name,
+            plan.modules.len()
+        );
+        if !drift.changed_on_replica.is_empty() {
+            msg.push_str(&format!(
+                " Changed on the replica since the last sync: {}.",
+                drift.changed_on_replica.join(", ")
+            ));
+        }
+        if !drift.pending_from_master.is_empty() {
+            msg.push_str(&format!(
+                " Not yet synced from the master: {}.",
+                drift.pending_from_master.join(", ")
+            ));
+        }
+        return format!("{}\n{}", msg, plan);
+    }
+
+    let push = async {
+        match critical {
+            Some(critical) => {
+                master_state
+                    .push_transactional(replica_client, mode, selection, critical)
+                    .await
+            }
+            None => {
+                master_state
+                    .push_selected(replica_client, mode, selection)
+                    .await
+            }
+        }
+    };
+    match history
+        .track(name, "manual", mode, master_state.fingerprints(), push)
+        .await
+    {
+        Ok(result) => {
+            let mut msg = format!("Replica {}: ", name);
+            if result.success {
+                msg.push_str("Successfully synced.");
+            } else {
+                msg.push_str(&format!(
+                    "Synced with errors. Failed modules: {}. Errors: {}",
+                    result.failed_modules.join(", "),
+                    result.errors.join("; ")
+                ));
+            }
+            if let Some(rollback) = &result.rollback {
+                if rollback.success {
+                    msg.push_str(&format!(
+                        " Rolled back: {}.",
+                        rollback.applied_modules.join(", ")
+                    ));
+                } else {
+                    msg.push_str(&format!(
+                        " Rollback failed: {}.",
+                        rollback.errors.join("; ")
+                    ));
+                }
+            }
+            if !result.skipped_modules.is_empty() {
+                msg.push_str(&format!(
+                    " Skipped by selection: {}.",
+                    result.skipped_modules.join(", ")
+                ));
+            }
+            msg
+        }
+        Err(e) => format!("Failed to connect or push to {}: {}", name, e),
+    }
+}
@@ -1,9 +1,14 @@
 use super::ToolRegistry;
+use crate::adguard::models::{QueryLogAnswer, QueryLogEntry, QueryLogParams};
 use crate::sync::SyncState;
+use futures::TryStreamExt;
 use std::path::PathBuf;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
+/// Largest page requested from `/control/querylog` in one call.
+const QUERY_LOG_PAGE_SIZE: u32 = 500;
+
 pub fn register(registry: &mut ToolRegistry) {
     registry.register(
         "manage_system",
@@ -18,15 +23,30 @@ pub fn register(registry: &mut ToolRegistry) {
                         "get_status", "get_stats", "clear_stats", "get_query_log",
                         "clear_query_log", "get_top_blocked_domains", "get_query_log_config",
                         "set_query_log_config", "get_version_info", "update_adguard_home",
-                        "create_backup", "restore_backup", "restore_backup_diff", "restart_service"
+                        "create_backup", "restore_backup", "restore_backup_diff", "restart_service",
+                        "get_capabilities", "get_stats_timeseries", "get_upstream_performance",
+                        "get_stats_config", "set_stats_config"
                     ]
                 },
                 "time_period": { "type": "string", "enum": ["24h", "7d", "30d"], "description": "For stats" },
                 "search": { "type": "string", "description": "Filter log by domain" },
-                "filter": { "type": "string", "enum": ["all", "blocked", "allowed"], "description": "Filter log by status" },
-                "limit": { "type": "integer", "minimum": 1, "maximum": 100, "description": "Max log entries" },
+                "filter": {
+                    "type": "string",
+                    "enum": [
+                        "all", "filtered", "blocked", "blocked_safebrowsing", "blocked_parental",
+                        "whitelisted", "rewritten", "safe_search", "processed", "allowed"
+                    ],
+                    "description": "Filter log by response status ('allowed' is an alias for 'processed')"
+                },
+                "client": { "type": "string", "description": "Only log entries from this client IP" },
+                "older_than": { "type": "string", "description": "RFC 3339 cursor: only entries older than this" },
+                "offset": { "type": "integer", "minimum": 0, "description": "Entries to skip" },
+                "since": { "type": "string", "description": "RFC 3339 time: page through the log back to this point" },
+                "limit": { "type": "integer", "minimum": 1, "description": "Max log entries" },
                 "enabled": { "type": "boolean", "description": "For query log config" },
-                "interval": { "type": "integer", "minimum": 1, "description": "Log retention hours" },
+                "interval": { "type": "integer", "minimum": 1, "description": "Query log or stats retention hours" },
+                "ignored": { "type": "array", "items": { "type": "string" }, "description": "Domains excluded from stats" },
+                "format": { "type": "string", "enum": ["sparkline", "series"], "description": "For get_stats_timeseries (default: sparkline)" },
                 "anonymize_client_ip": { "type": "boolean" },
                 "allowed_clients": { "type": "array", "items": { "type": "string" } },
                 "disallowed_clients": { "type": "array", "items": { "type": "string" } },
@@ -77,18 +97,49 @@ pub fn register(registry: &mut ToolRegistry) {
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Stats cleared" }] }))
                     }
                     "get_query_log" => {
-                        let search = params["search"].as_str();
-                        let filter = params["filter"].as_str();
+                        let filter = params["filter"].as_str().map(|f| if f == "allowed" { "processed" } else { f });
                         let limit = params["limit"].as_u64().map(|l| l as u32);
-                        let log = client.get_query_log(search, filter, limit).await?;
+                        let since = match params["since"].as_str() {
+                            Some(s) => Some(
+                                chrono::DateTime::parse_from_rfc3339(s)
+                                    .map_err(|e| crate::error::Error::Generic(format!("Invalid since '{}': {}", s, e)))?
+                                    .with_timezone(&chrono::Utc),
+                            ),
+                            None => None,
+                        };
+                        let query = QueryLogParams {
+                            search: params["search"].as_str().map(|s| s.to_string()),
+                            response_status: filter.map(|f| f.to_string()),
+                            older_than: params["older_than"].as_str().map(|s| s.to_string()),
+                            offset: params["offset"].as_u64().map(|o| o as u32),
+                            limit: limit.map(|l| l.min(QUERY_LOG_PAGE_SIZE)),
+                            client: params["client"].as_str().map(|s| s.to_string()),
+                        };
+
+                        let entries = if since.is_some() || limit.is_some_and(|l| l > QUERY_LOG_PAGE_SIZE) {
+                            let max = limit.map(|l| l as usize).unwrap_or(usize::MAX);
+                            let mut entries = Vec::new();
+                            let mut pages = std::pin::pin!(client.query_log_pages(query, since));
+                            while let Some(page) = pages.try_next().await? {
+                                entries.extend(page);
+                                if entries.len() >= max {
+                                    entries.truncate(max);
+                                    break;
+                                }
+                            }
+                            entries
+                        } else {
+                            client.query_log(&query).await?.data
+                        };
+
                         let mut text = String::new();
-                        for entry in log.data {
-                            text.push_str(&format!(
-                                "[{}] {} -> {} ({}, {}ms)\n",
-                                entry.time, entry.question.name, entry.status, entry.reason, entry.elapsed_ms
-                            ));
+                        for entry in &entries {
+                            text.push_str(&format_query_log_entry(entry));
                         }
                         if text.is_empty() { text = "No entries found".to_string(); }
+                        else if let Some(last) = entries.last() {
+                            text.push_str(&format!("---\n{} entries; next page: older_than={}\n", entries.len(), last.time));
+                        }
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
                     }
                     "clear_query_log" => {
@@ -110,6 +161,69 @@ pub fn register(registry: &mut ToolRegistry) {
                         }
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
                     }
+                    "get_stats_timeseries" => {
+                        let period = params["time_period"].as_str();
+                        let stats = client.get_stats(period).await?;
+                        let series = [
+                            ("DNS Queries", &stats.dns_queries),
+                            ("Blocked", &stats.blocked_filtering),
+                            ("Safe Browsing", &stats.replaced_safebrowsing),
+                            ("Parental", &stats.replaced_parental),
+                        ];
+                        if params["format"].as_str() == Some("series") {
+                            let json = serde_json::json!({
+                                "time_units": stats.time_units,
+                                "dns_queries": stats.dns_queries,
+                                "blocked_filtering": stats.blocked_filtering,
+                                "replaced_safebrowsing": stats.replaced_safebrowsing,
+                                "replaced_parental": stats.replaced_parental,
+                            });
+                            return Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string(&json)? }] }));
+                        }
+                        let units = if stats.time_units.is_empty() { "buckets" } else { stats.time_units.as_str() };
+                        let mut text = format!("Time series (oldest to newest, {} per point):\n", units);
+                        for (name, values) in series {
+                            let total: u64 = values.iter().sum();
+                            let peak = values.iter().copied().max().unwrap_or(0);
+                            text.push_str(&format!("{}: {} (total {}, peak {})\n", name, sparkline(values), total, peak));
+                        }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "get_upstream_performance" => {
+                        let period = params["time_period"].as_str();
+                        let stats = client.get_stats(period).await?;
+                        let avg_times: std::collections::HashMap<&String, f64> = stats
+                            .top_upstreams_avg_time
+                            .iter()
+                            .flatten()
+                            .map(|(upstream, time)| (upstream, *time))
+                            .collect();
+                        let mut text = "Upstream Performance:\n".to_string();
+                        if stats.top_upstreams_responses.is_empty() {
+                            text.push_str("None found.\n");
+                        }
+                        for (upstream, count) in stats.top_upstreams_responses.iter().flatten() {
+                            match avg_times.get(upstream) {
+                                Some(avg) => text.push_str(&format!("- {}: {} responses, avg {:.2}ms\n", upstream, count, avg * 1000.0)),
+                                None => text.push_str(&format!("- {}: {} responses\n", upstream, count)),
+                            }
+                        }
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+                    }
+                    "get_stats_config" => {
+                        let config = client.get_stats_config().await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&config)? }] }))
+                    }
+                    "set_stats_config" => {
+                        let mut config = client.get_stats_config().await?;
+                        if let Some(e) = params["enabled"].as_bool() { config.enabled = e; }
+                        if let Some(i) = params["interval"].as_u64() { config.interval = hours_to_ms(i)?; }
+                        if let Some(ignored) = params["ignored"].as_array() {
+                            config.ignored = ignored.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
+                        }
+                        client.set_stats_config(config).await?;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": "Stats config updated" }] }))
+                    }
                     "get_query_log_config" => {
                         let config = client.get_query_log_config().await?;
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&config)? }] }))
@@ -117,7 +231,7 @@ pub fn register(registry: &mut ToolRegistry) {
                     "set_query_log_config" => {
                         let mut config = client.get_query_log_config().await?;
                         if let Some(e) = params["enabled"].as_bool() { config.enabled = e; }
-                        if let Some(i) = params["interval"].as_u64() { config.interval = i; }
+                        if let Some(i) = params["interval"].as_u64() { config.interval = hours_to_ms(i)?; }
                         if let Some(a) = params["anonymize_client_ip"].as_bool() { config.anonymize_client_ip = a; }
                         if let Some(allowed) = params["allowed_clients"].as_array() {
                             config.allowed_clients = allowed.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
@@ -211,6 +325,10 @@ pub fn register(registry: &mut ToolRegistry) {
 
                         Ok(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
                     }
+                    "get_capabilities" => {
+                        let caps = client.capabilities().await;
+                        Ok(serde_json::json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&caps)? }] }))
+                    }
                     "restart_service" => {
                         let hard = params["force"].as_bool().unwrap_or(false);
                         client.restart_service(hard).await?;
@@ -227,3 +345,79 @@ pub fn register(registry: &mut ToolRegistry) {
         },
     );
 }
+
+/// Retention hours from tool arguments as the milliseconds the API expects.
+fn hours_to_ms(hours: u64) -> crate::error::Result<u64> {
+    hours
+        .checked_mul(60 * 60 * 1000)
+        .ok_or_else(|| super::invalid_params(format!("Interval of {} hours is too large", hours)))
+}
+
+fn sparkline(values: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return BARS[0].to_string().repeat(values.len());
+    }
+    values
+        .iter()
+        .map(|v| BARS[((v * 7) / max) as usize])
+        .collect()
+}
+
+fn format_answers(answers: &[QueryLogAnswer]) -> String {
+    answers
+        .iter()
+        .map(|a| format!("{} {} (ttl {})", a.rtype, a.value, a.ttl))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_query_log_entry(entry: &QueryLogEntry) -> String {
+    let mut text = format!(
+        "[{}] {} -> {} ({}, {}ms)\n",
+        entry.time, entry.question.name, entry.status, entry.reason, entry.elapsed_ms
+    );
+
+    let mut client = entry.client.clone();
+    if let Some(name) = entry
+        .client_info
+        .as_ref()
+        .map(|i| &i.name)
+        .filter(|n| !n.is_empty())
+    {
+        client.push_str(&format!(" ({})", name));
+    }
+    if !entry.client_proto.is_empty() {
+        client.push_str(&format!(" via {}", entry.client_proto));
+    }
+    text.push_str(&format!("  Client: {}\n", client));
+
+    if !entry.answer.is_empty() {
+        text.push_str(&format!("  Answer: {}\n", format_answers(&entry.answer)));
+    }
+    if !entry.original_answer.is_empty() {
+        text.push_str(&format!(
+            "  Original Answer: {}\n",
+            format_answers(&entry.original_answer)
+        ));
+    }
+    if entry.cached {
+        text.push_str("  Upstream: (served from cache)\n");
+    } else if !entry.upstream.is_empty() {
+        text.push_str(&format!("  Upstream: {}\n", entry.upstream));
+    }
+    if entry.answer_dnssec {
+        text.push_str("  DNSSEC: validated\n");
+    }
+    for rule in &entry.rules {
+        text.push_str(&format!(
+            "  Rule: {} (filter list {})\n",
+            rule.text, rule.filter_list_id
+        ));
+    }
+    if !entry.service_name.is_empty() {
+        text.push_str(&format!("  Service: {}\n", entry.service_name));
+    }
+    text
+}